@@ -0,0 +1,245 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Frame by frame voice analysis of the speech in a clip
+use clap::ValueEnum;
+use color_eyre::eyre::{Report, Result, eyre};
+use crate::audio_clips::{AudioClip, Speech};
+use crate::breathiness::SpectralAnalyser;
+use crate::formants::FormantTracker;
+use crate::pitch::PitchTracker;
+use crate::resonance;
+
+/// Step between analysis frames in seconds
+pub const HOP: f64 = 0.01;
+
+/// A quantity measured on every voiced frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Metric {
+    /// Fundamental frequency
+    Pitch,
+    /// First formant
+    F1,
+    /// Second formant
+    F2,
+    /// Third formant
+    F3,
+    /// Smoothed cepstral peak prominence; lower values sound breathier
+    Cpps,
+    /// Level difference between the first two harmonics; higher values sound breathier
+    #[value(name = "h1-h2")]
+    H1H2,
+    /// Slope of the spectrum up to 5 kHz; steeper (more negative) values sound softer
+    Tilt,
+    /// Power weighted mean frequency of the spectrum up to 5 kHz; higher values sound brighter
+    Centroid,
+    /// Vocal tract length estimated from the spacing of the formants
+    Vtl,
+    /// Brightness score from 0 (dark) to 100 (bright) combining vocal tract length, centroid and F2
+    Brightness,
+    /// RMS level of the frame relative to digital full scale
+    Level,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 11] = [
+        Metric::Pitch, Metric::F1, Metric::F2, Metric::F3, Metric::Cpps, Metric::H1H2, Metric::Tilt,
+        Metric::Centroid, Metric::Vtl, Metric::Brightness, Metric::Level,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Pitch => "pitch",
+            Metric::F1 => "f1",
+            Metric::F2 => "f2",
+            Metric::F3 => "f3",
+            Metric::Cpps => "cpps",
+            Metric::H1H2 => "h1-h2",
+            Metric::Tilt => "tilt",
+            Metric::Centroid => "centroid",
+            Metric::Vtl => "vtl",
+            Metric::Brightness => "brightness",
+            Metric::Level => "level",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Metric::Pitch | Metric::F1 | Metric::F2 | Metric::F3 | Metric::Centroid => "Hz",
+            Metric::Cpps | Metric::H1H2 => "dB",
+            Metric::Tilt => "dB/kHz",
+            Metric::Vtl => "cm",
+            Metric::Brightness => "/100",
+            Metric::Level => "dBFS",
+        }
+    }
+
+    /// Whether the metric is a frequency, which is perceived on a logarithmic scale
+    pub fn is_frequency(&self) -> bool {
+        matches!(self, Metric::Pitch | Metric::F1 | Metric::F2 | Metric::F3 | Metric::Centroid)
+    }
+
+    /// The value of this metric in a frame, if it could be measured there
+    pub fn value(&self, frame: &AnalysisFrame) -> Option<f32> {
+        match self {
+            Metric::Pitch => Some(frame.f0),
+            Metric::F1 => frame.formants.map(|formants| formants[0]),
+            Metric::F2 => frame.formants.map(|formants| formants[1]),
+            Metric::F3 => frame.formants.map(|formants| formants[2]),
+            Metric::Cpps => frame.cpps,
+            Metric::H1H2 => frame.h1_h2,
+            Metric::Tilt => frame.tilt,
+            Metric::Centroid => frame.centroid,
+            Metric::Vtl => frame.formants.map(resonance::vocal_tract_length),
+            Metric::Brightness => Some(resonance::brightness(frame.centroid?, frame.formants?)),
+            Metric::Level => Some(frame.level),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Metric {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Metric> {
+        Metric::ALL.into_iter()
+            .find(|metric| metric.name() == s)
+            .ok_or(eyre!("Unknown metric '{}'", s))
+    }
+}
+
+/// Measurements of one voiced frame
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisFrame {
+    /// Centre of the frame in seconds from the start of the edited clip
+    pub time: f64,
+    /// Fundamental frequency in Hz
+    pub f0: f32,
+    /// RMS level in dBFS
+    pub level: f32,
+    /// First three formants in Hz, if they could be estimated
+    pub formants: Option<[f32; 3]>,
+    /// Smoothed cepstral peak prominence in dB
+    pub cpps: Option<f32>,
+    /// First minus second harmonic level in dB
+    pub h1_h2: Option<f32>,
+    /// Spectral slope up to 5 kHz in dB per kHz
+    pub tilt: Option<f32>,
+    /// Spectral centroid up to 5 kHz in Hz
+    pub centroid: Option<f32>,
+}
+
+/// The voiced frames of a clip's speech segments
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub frames: Vec<AnalysisFrame>,
+}
+
+impl Analysis {
+    pub fn of(audio_clip: &AudioClip) -> Result<Analysis> {
+        Ok(Analysis::of_speech(audio_clip, &audio_clip.speech()?))
+    }
+
+    /// Analyse speech already taken from the clip
+    pub fn of_speech(audio_clip: &AudioClip, speech: &Speech) -> Analysis {
+        let samples = &speech.samples;
+        let sample_rate = audio_clip.sample_rate as f64;
+        let pitch_tracker = PitchTracker::new(audio_clip.sample_rate);
+        let formant_tracker = FormantTracker::new(samples, audio_clip.sample_rate);
+        let mut spectral_analyser = SpectralAnalyser::new(audio_clip.sample_rate);
+
+        let mut frames = Vec::new();
+        for segment in speech.segments.iter().cloned() {
+            let offset = segment.start as f64 / sample_rate;
+            let mut cepstra = Vec::new();
+            for pitch in pitch_tracker.track(&samples[segment], HOP) {
+                let Some(f0) = pitch.f0 else { continue };
+                let time = offset + pitch.time;
+                let spectral = spectral_analyser.analyse(samples, (time * sample_rate) as usize, f0);
+                let (h1_h2, tilt, centroid) = match spectral {
+                    Some((spectral, cepstrum)) => {
+                        cepstra.push((frames.len(), cepstrum));
+                        (Some(spectral.h1_h2), Some(spectral.tilt), Some(spectral.centroid))
+                    }
+                    None => (None, None, None),
+                };
+                frames.push(AnalysisFrame { time, f0, level: pitch.level, formants: formant_tracker.estimate(time), cpps: None, h1_h2, tilt, centroid });
+            }
+
+            // Cepstral smoothing runs over the neighbouring frames of the segment
+            let (indices, cepstra): (Vec<usize>, Vec<Vec<f32>>) = cepstra.into_iter().unzip();
+            for (index, cpps) in indices.into_iter().zip(spectral_analyser.cpps(&cepstra)) {
+                frames[index].cpps = cpps;
+            }
+        }
+        Analysis { frames }
+    }
+
+    /// The values of a metric over the frames where it could be measured
+    pub fn values(&self, metric: Metric) -> Vec<f32> {
+        self.frames.iter().filter_map(|frame| metric.value(frame)).collect()
+    }
+
+    pub fn median(&self, metric: Metric) -> Option<f32> {
+        let mut values = self.values(metric);
+        values.sort_by(f32::total_cmp);
+        values.get(values.len() / 2).copied()
+    }
+
+    /// Share of the measured voiced time (0 to 1) where `metric` lies between `min` and `max`
+    pub fn in_range(&self, metric: Metric, min: f32, max: f32) -> Option<f32> {
+        let values = self.values(metric);
+        if values.is_empty() {
+            return None;
+        }
+        let inside = values.iter().filter(|value| (min..=max).contains(*value)).count();
+        Some(inside as f32 / values.len() as f32)
+    }
+
+    /// The frames between `start` and `end` seconds
+    pub fn between(&self, start: f64, end: f64) -> Analysis {
+        let frames = self.frames.iter().filter(|frame| (start..end).contains(&frame.time)).copied().collect();
+        Analysis { frames }
+    }
+
+    /// The frames at most `within` seconds from `time`, found by binary search since the frames are sorted by time
+    pub fn frames_near(&self, time: f64, within: f64) -> &[AnalysisFrame] {
+        let start = self.frames.partition_point(|frame| frame.time < time - within);
+        let end = self.frames.partition_point(|frame| frame.time <= time + within);
+        &self.frames[start..end.max(start)]
+    }
+
+    /// Total voiced time in seconds
+    pub fn voiced_time(&self) -> f64 {
+        self.frames.len() as f64 * HOP
+    }
+}
+
+/// Analyses a clip the first time the analysis is needed
+pub struct LazyAnalysis<'a> {
+    pub audio_clip: &'a AudioClip,
+    analysis: Option<Analysis>,
+}
+
+impl<'a> LazyAnalysis<'a> {
+    pub fn new(audio_clip: &'a AudioClip) -> LazyAnalysis<'a> {
+        LazyAnalysis { audio_clip, analysis: None }
+    }
+
+    /// Start from an analysis that was already made
+    pub fn with(audio_clip: &'a AudioClip, analysis: Analysis) -> LazyAnalysis<'a> {
+        LazyAnalysis { audio_clip, analysis: Some(analysis) }
+    }
+
+    pub fn get(&mut self) -> Result<&Analysis> {
+        if self.analysis.is_none() {
+            self.analysis = Some(Analysis::of(self.audio_clip)?);
+        }
+        Ok(self.analysis.as_ref().unwrap())
+    }
+}
@@ -2,13 +2,29 @@ use std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::sync::atomic::{AtomicBool, Ordering};
-use ctrlc;
 
 /// Raw mono audio clips
 use color_eyre::eyre::{Result, eyre};
 use cpal::{ChannelCount, FromSample, Sample, StreamConfig, Device, SampleFormat, Stream};
 use dasp::{signal, Signal};
 use dasp::interpolate::linear::Linear;
+use std::path::Path;
+use std::io::Write;
+use std::ops::Range;
+use std::borrow::Cow;
+use crate::denoise::NoiseProfile;
+use crate::edits::{Edit, EditList, NoiseReduction};
+use crate::effects::Chain;
+use crate::loudness::Loudness;
+use crate::shift;
+use crate::stretch::{self, Wsola};
+use std::collections::VecDeque;
+use crate::vad::{self, VadConfig};
+
+/// Vorbis encoder quality, from 0.0 to 1.0
+const OGG_QUALITY: f32 = 0.4;
+/// How much the speed controls change the playback speed per step
+const SPEED_STEP: f64 = 0.1;
 
 #[derive(Debug, Clone)]
 pub struct AudioClip {
@@ -18,14 +34,55 @@ pub struct AudioClip {
     pub samples: Vec<f32>,
     pub sample_rate: u32, // 48khz and
     pub playback_position: usize, // Track playback position
+    pub edits: EditList, // Non-destructive edits on top of `samples`
+    pub reference: bool, // Exemplar of a target voice rather than a journal entry
+    pub exercise: Option<String>, // Name of the practice exercise the clip was recorded for
+}
+
+/// Analysis samples of a clip with the ranges of them that contain speech. Getting them reduces the noise, so
+/// a command gets them once per clip and hands them to every analyser
+pub struct Speech<'a> {
+    pub samples: Cow<'a, [f32]>,
+    pub segments: Vec<Range<usize>>,
 }
 
 impl AudioClip {
     pub fn record(name: String) -> Result<AudioClip> {
+        let (stream, clip) = AudioClip::start_recording(name)?;
+        println!("Beginning recording");
+        println!("Press Ctrl+C to stop recording");
+
+        // Wait for Ctrl+C
+        let recording = running_until_ctrl_c()?;
+        while recording.load(Ordering::SeqCst) {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        let clip = AudioClip::finish_recording(stream, &clip)?;
+        println!("\nFinished recording");
+        println!("Recording length: {} seconds", clip.samples.len() as f32 / clip.sample_rate as f32);
+        Ok(clip)
+    }
+
+    /// Record for at most `duration` seconds, stopping early when `running` is cleared (e.g. by Ctrl+C)
+    pub fn record_timed(name: String, duration: f64, running: &AtomicBool) -> Result<AudioClip> {
+        let (stream, clip) = AudioClip::start_recording(name)?;
+        let started = std::time::Instant::now();
+        while running.load(Ordering::SeqCst) && started.elapsed().as_secs_f64() < duration {
+            print!("\rRecording... {:.0}s left ", (duration - started.elapsed().as_secs_f64()).ceil());
+            std::io::stdout().flush()?;
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        println!();
+        AudioClip::finish_recording(stream, &clip)
+    }
+
+    /// Open the default input device and start capturing into a new clip shared with the audio thread
+    pub fn start_recording(name: String) -> Result<(Stream, ClipHandle)> {
         // Setup input device
         let (device, config) = setup_audio_device(true)?;
-        let sample_rate = config.sample_rate().0 as u32;
-        
+        let sample_rate = config.sample_rate().0;
+
         let samples = Vec::new();
         let audio_clip = AudioClip {
             id: None,
@@ -34,91 +91,268 @@ impl AudioClip {
             samples,
             sample_rate,
             playback_position: 0,
+            edits: EditList::default(),
+            reference: false,
+            exercise: None,
         };
-        println!("Beginning recording");
-        println!("Press Ctrl+C to stop recording");
-        
+
         let clip = Arc::new(Mutex::new(Some(audio_clip)));
-        let clip2 = clip.clone();
 
         let channels = config.channels();
         let sample_format = config.sample_format();
         let stream_config = config.into();
 
         // Build input stream
-        let stream = build_audio_stream(
-            &device, 
-            &stream_config, 
-            channels, 
-            &clip2, 
-            true,
+        let stream = build_input_stream(
+            &device,
+            &stream_config,
+            channels,
+            &clip,
             sample_format,
         )?;
-
-        // Set up Ctrl+C handler
-        let recording = Arc::new(AtomicBool::new(true));
-        let recording_for_handler = recording.clone();
-        
-        ctrlc::set_handler(move || {
-            recording_for_handler.store(false, Ordering::SeqCst);
-        })?;
-
         stream.play()?;
-        
-        // Wait for Ctrl+C
-        while recording.load(Ordering::SeqCst) {
-            std::thread::sleep(std::time::Duration::from_millis(100));
-        }
-        
+        Ok((stream, clip))
+    }
+
+    /// Stop the input stream and take the recorded clip out of the shared handle
+    pub fn finish_recording(stream: Stream, clip: &ClipHandle) -> Result<AudioClip> {
         drop(stream);
-        let clip = clip.lock().unwrap().take().unwrap();
-        println!("\nFinished recording");
-        println!("Recording length: {} seconds", clip.samples.len() as f32 / clip.sample_rate as f32);
+        let clip = clip.lock().unwrap().take().ok_or(eyre!("Recording was already finished"))?;
         Ok(clip)
     }
-    
+
+    /// The samples left after applying the clip's edits, leaving out noise reduction only meant for analysis
+    pub fn edited_samples(&self) -> Result<Cow<'_, [f32]>> {
+        self.processed_samples(false)
+    }
+
+    /// The edited samples with all noise reduction applied. Analysers should look at these
+    pub fn analysis_samples(&self) -> Result<Cow<'_, [f32]>> {
+        self.processed_samples(true)
+    }
+
+    // Apply the edits. Noise is reduced over the whole recording before the kept range is cut out, so the
+    // edges of the clip are filtered with their real surroundings
+    fn processed_samples(&self, analysis: bool) -> Result<Cow<'_, [f32]>> {
+        let (range, reductions) = self.edits.resolve(self.samples.len(), self.sample_rate)?;
+        let reductions: Vec<NoiseReduction> = reductions.into_iter().filter(|reduction| analysis || !reduction.analysis_only).collect();
+        if reductions.is_empty() {
+            return Ok(Cow::Borrowed(&self.samples[range]));
+        }
+        let mut samples = self.samples.clone();
+        for reduction in reductions {
+            let profile = match reduction.noise {
+                Some(noise) => NoiseProfile::learn(&samples, self.sample_rate, &[noise])
+                    .ok_or(eyre!("The noise region of clip '{}' is too short to learn the noise from", self.name))?,
+                None => NoiseProfile::from_silence(&samples[reduction.scope], self.sample_rate)
+                    .ok_or(eyre!("Clip '{}' has too little silence to learn the noise from, give a noise region instead", self.name))?,
+            };
+            samples = profile.reduce(&samples, reduction.reduction);
+        }
+        Ok(Cow::Owned(samples[range].to_vec()))
+    }
+
+    /// A copy of the clip with its edits applied to the samples
+    pub fn edited(&self) -> Result<AudioClip> {
+        Ok(AudioClip {
+            samples: self.edited_samples()?.into_owned(),
+            edits: EditList::default(),
+            playback_position: 0,
+            ..self.clone()
+        })
+    }
+
+    /// A copy of the edited clip with its pitch and formant frequencies multiplied by the given ratios, keeping its duration
+    pub fn shifted(&self, pitch_ratio: f64, formant_ratio: f64) -> Result<AudioClip> {
+        let mut audio_clip = self.edited()?;
+        audio_clip.samples = shift::shift(&audio_clip.samples, audio_clip.sample_rate, pitch_ratio, formant_ratio);
+        Ok(audio_clip)
+    }
+
+    /// A copy of the edited clip run through an effect chain
+    pub fn with_effects(&self, chain: &Chain) -> Result<AudioClip> {
+        let mut audio_clip = self.edited()?;
+        chain.apply(&mut audio_clip.samples, audio_clip.sample_rate)?;
+        Ok(audio_clip)
+    }
+
+    /// Append an edit, checking that it fits inside the currently edited audio
+    pub fn add_edit(&mut self, edit: Edit) -> Result<()> {
+        self.edits.0.push(edit);
+        // Noise reduction can only fail once the noise is learned, so it is checked by running it
+        let check = match edit {
+            Edit::Denoise { .. } => self.analysis_samples().map(|_| ()),
+            _ => self.edits.apply(self.samples.len(), self.sample_rate).map(|_| ()),
+        };
+        if let Err(e) = check {
+            self.edits.0.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Seconds of the recording cut off the start by the clip's edits
+    pub fn edit_offset(&self) -> Result<f64> {
+        let range = self.edits.apply(self.samples.len(), self.sample_rate)?;
+        Ok(range.start as f64 / self.sample_rate as f64)
+    }
+
+    /// Length of the edited clip in seconds
+    pub fn duration(&self) -> Result<f64> {
+        Ok(self.edits.apply(self.samples.len(), self.sample_rate)?.len() as f64 / self.sample_rate as f64)
+    }
+
+    /// Ranges of the analysis samples that contain speech. Analysers should only look at these
+    pub fn speech_segments(&self) -> Result<Vec<Range<usize>>> {
+        Ok(self.speech()?.segments)
+    }
+
+    /// The analysis samples together with their speech segments
+    pub fn speech(&self) -> Result<Speech<'_>> {
+        let samples = self.analysis_samples()?;
+        let segments = vad::speech_segments(&samples, self.sample_rate, &VadConfig::default());
+        Ok(Speech { samples, segments })
+    }
+
+    /// Add a trim edit removing the silence before the first and after the last speech segment, keeping `padding` seconds of it.
+    /// Returns false if no speech was found
+    pub fn trim_silence(&mut self, padding: f64) -> Result<bool> {
+        let segments = self.speech_segments()?;
+        let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+            return Ok(false);
+        };
+        let sample_rate = self.sample_rate as f64;
+        let duration = self.duration()?;
+        // Round to milliseconds so the stored edit stays readable
+        let start = ((first.start as f64 / sample_rate - padding).max(0.0) * 1000.0).round() / 1000.0;
+        let end = ((last.end as f64 / sample_rate + padding) * 1000.0).round() / 1000.0;
+        let end = end.min(duration);
+        if start > 0.0 || end < duration {
+            self.add_edit(Edit::Crop { start, end })?;
+        }
+        Ok(true)
+    }
+
+    /// Loudness of the edited clip
+    pub fn loudness(&self) -> Result<Loudness> {
+        Ok(Loudness::measure(&self.edited_samples()?, self.sample_rate))
+    }
+
+    /// Scale the samples in memory so the edited clip has an integrated loudness of `target` LUFS.
+    /// Returns the applied gain in dB and whether it was limited to keep the true peak down, or None if the clip is silent
+    pub fn normalize(&mut self, target: f64) -> Result<Option<(f64, bool)>> {
+        let gain_db = self.loudness()?.gain_to(target);
+        if let Some((gain_db, _)) = gain_db {
+            let gain = 10f32.powf(gain_db as f32 / 20.0);
+            for sample in self.samples.iter_mut() {
+                *sample *= gain;
+            }
+        }
+        Ok(gain_db)
+    }
+
+    /// Read a WAV file into a new clip, mixing all channels down to mono
+    pub fn import_wav(path: &Path, name: String) -> Result<AudioClip> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.samples::<i32>().map(|sample| sample.map(|sample| sample as f32 / scale)).collect::<Result<_, _>>()?
+            }
+        };
+        let channels = spec.channels.max(1) as usize;
+        let samples = interleaved.chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+        Ok(AudioClip {
+            id: None,
+            name,
+            created_at: Utc::now(),
+            samples,
+            sample_rate: spec.sample_rate,
+            playback_position: 0,
+            edits: EditList::default(),
+            reference: false,
+            exercise: None,
+        })
+    }
+
+    /// Write the edited clip to a 32-bit float mono WAV file
+    pub fn export_wav(&self, path: &Path) -> Result<()> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        for &sample in self.edited_samples()?.iter() {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+
+    /// Encode the edited clip as an Ogg Vorbis stream
+    pub fn encode_ogg(&self) -> Result<Vec<u8>> {
+        let samples: Vec<i16> = self.edited_samples()?.iter()
+            .map(|&sample| (sample * 32767.0).clamp(-32768.0, 32767.0) as i16)
+            .collect();
+        let mut encoder = vorbis_encoder::Encoder::new(1, self.sample_rate as u64, OGG_QUALITY)
+            .map_err(|e| eyre!("Failed to create Vorbis encoder: {}", e))?;
+        let mut ogg = encoder.encode(&samples).map_err(|e| eyre!("Failed to encode audio: {}", e))?;
+        ogg.extend(encoder.flush().map_err(|e| eyre!("Failed to flush encoder: {}", e))?);
+        Ok(ogg)
+    }
+
     pub fn play(&self) -> Result<()> {
+        self.play_at(1.0, false)
+    }
+
+    /// Play the edited clip at `speed` without changing its pitch. With `controls`, the speed can be changed
+    /// from the keyboard while playing
+    pub fn play_at(&self, speed: f64, controls: bool) -> Result<()> {
         println!("Playing audio clip");
         // Setup output device
         let (device, config) = setup_audio_device(false)?;
         
         // Get the output device sample rate
-        let output_sample_rate = config.sample_rate().0 as u32;
+        let output_sample_rate = config.sample_rate().0;
         
         // Resample the audio clip to match the output device sample rate
-        let resampled_clip = self.resample(output_sample_rate)?;
+        let resampled_clip = self.edited()?.resample(output_sample_rate)?;
         println!("Resampled from {}Hz to {}Hz", self.sample_rate, output_sample_rate);
 
-        // Calculate playback duration based on sample count and sample rate
-        let playback_duration = std::time::Duration::from_secs_f32(
-            resampled_clip.samples.len() as f32 / resampled_clip.sample_rate as f32
-        );
-
         println!("Beginning playback");
-        let clip = Arc::new(Mutex::new(Some(resampled_clip)));
-        let clip2 = clip.clone();
+        let playback = Arc::new(Mutex::new(Playback::new(resampled_clip, speed)));
+        let stopped = Arc::new(AtomicBool::new(false));
+        if controls {
+            println!("Type + or - and Enter to play faster or slower, a number for a speed, q to stop");
+            let (playback, stopped) = (playback.clone(), stopped.clone());
+            std::thread::spawn(move || speed_controls(&playback, &stopped));
+        }
 
         let channels = config.channels();
         let sample_format = config.sample_format();
         let stream_config = config.into();
 
         // Build output stream
-        let stream = build_audio_stream(
+        let stream = build_output_stream(
             &device, 
             &stream_config, 
             channels, 
-            &clip2, 
-            false,
+            &playback, 
             sample_format,
         )?;
 
         stream.play()?;
-        
-        // Add a small buffer to ensure all audio is played
-        let buffer_duration = std::time::Duration::from_millis(500);
-        
-        println!("Playback duration: {:?}", playback_duration);
-        std::thread::sleep(playback_duration + buffer_duration);
+        while !stopped.load(Ordering::SeqCst) && !playback.lock().unwrap().is_finished() {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        if !stopped.load(Ordering::SeqCst) {
+            // Add a small buffer to ensure all audio is played
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
         println!("Playback complete");
 
         Ok(())
@@ -143,10 +377,23 @@ impl AudioClip {
             samples,
             sample_rate,
             playback_position: 0,
+            edits: self.edits.clone(),
+            reference: self.reference,
+            exercise: self.exercise.clone(),
         })
     }
 }
 
+/// A flag that stays true until Ctrl+C is pressed
+pub fn running_until_ctrl_c() -> Result<Arc<AtomicBool>> {
+    let running = Arc::new(AtomicBool::new(true));
+    let running_for_handler = running.clone();
+    ctrlc::set_handler(move || {
+        running_for_handler.store(false, Ordering::SeqCst);
+    })?;
+    Ok(running)
+}
+
 // Common function to set up audio device
 fn setup_audio_device(is_input: bool) -> Result<(Device, cpal::SupportedStreamConfig)> {
     let host = cpal::default_host();
@@ -175,93 +422,149 @@ fn create_error_fn() -> impl FnMut(cpal::StreamError) + Send + 'static {
     }
 }
 
-// Build audio stream based on sample format
-fn build_audio_stream(
+// Build an input stream recording into `clip`, based on sample format
+fn build_input_stream(
     device: &Device,
     config: &StreamConfig,
     channels: ChannelCount,
     clip: &ClipHandle,
-    is_input: bool,
     sample_format: SampleFormat,
 ) -> Result<Stream> {
     let err_fn = create_error_fn();
     let clip = clip.clone();
     
     let stream = match sample_format {
-        SampleFormat::F32 => {
-            if is_input {
-                device.build_input_stream(
-                    config,
-                    move |data, _: &_| write_input_data::<f32>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            } else {
-                device.build_output_stream(
-                    config,
-                    move |data, _: &_| write_output_data::<f32>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            }
-        },
-        SampleFormat::I16 => {
-            if is_input {
-                device.build_input_stream(
-                    config,
-                    move |data, _: &_| write_input_data::<i16>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            } else {
-                device.build_output_stream(
-                    config,
-                    move |data, _: &_| write_output_data::<i16>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            }
-        },
-        SampleFormat::U16 => {
-            if is_input {
-                device.build_input_stream(
-                    config,
-                    move |data, _: &_| write_input_data::<u16>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            } else {
-                device.build_output_stream(
-                    config,
-                    move |data, _: &_| write_output_data::<u16>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            }
-        },
-        _ => {
-            if is_input {
-                device.build_input_stream(
-                    config,
-                    move |data, _: &_| write_input_data::<f32>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            } else {
-                device.build_output_stream(
-                    config,
-                    move |data, _: &_| write_output_data::<f32>(data, channels, &clip),
-                    err_fn,
-                    None,
-                )?
-            }
-        },
+        SampleFormat::I16 => device.build_input_stream(
+            config,
+            move |data, _: &_| write_input_data::<i16>(data, channels, &clip),
+            err_fn,
+            None,
+        )?,
+        SampleFormat::U16 => device.build_input_stream(
+            config,
+            move |data, _: &_| write_input_data::<u16>(data, channels, &clip),
+            err_fn,
+            None,
+        )?,
+        _ => device.build_input_stream(
+            config,
+            move |data, _: &_| write_input_data::<f32>(data, channels, &clip),
+            err_fn,
+            None,
+        )?,
     };
     
     Ok(stream)
 }
 
-type ClipHandle = Arc<Mutex<Option<AudioClip>>>;
+// Build an output stream playing `playback`, based on sample format
+fn build_output_stream(
+    device: &Device,
+    config: &StreamConfig,
+    channels: ChannelCount,
+    playback: &PlaybackHandle,
+    sample_format: SampleFormat,
+) -> Result<Stream> {
+    let err_fn = create_error_fn();
+    let playback = playback.clone();
+
+    let stream = match sample_format {
+        SampleFormat::I16 => device.build_output_stream(
+            config,
+            move |data, _: &_| write_output_data::<i16>(data, channels, &playback),
+            err_fn,
+            None,
+        )?,
+        SampleFormat::U16 => device.build_output_stream(
+            config,
+            move |data, _: &_| write_output_data::<u16>(data, channels, &playback),
+            err_fn,
+            None,
+        )?,
+        _ => device.build_output_stream(
+            config,
+            move |data, _: &_| write_output_data::<f32>(data, channels, &playback),
+            err_fn,
+            None,
+        )?,
+    };
+
+    Ok(stream)
+}
+
+/// A clip shared between the audio thread and the rest of the program
+pub type ClipHandle = Arc<Mutex<Option<AudioClip>>>;
+
+/// A clip being played, time stretched on the fly so its speed can change while playing
+pub struct Playback {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    stretcher: Wsola,
+    pending: VecDeque<f32>,
+    speed: f64,
+    finished: bool,
+}
+
+impl Playback {
+    fn new(audio_clip: AudioClip, speed: f64) -> Playback {
+        Playback {
+            stretcher: Wsola::new(audio_clip.sample_rate),
+            samples: audio_clip.samples,
+            sample_rate: audio_clip.sample_rate,
+            pending: VecDeque::new(),
+            speed,
+            finished: false,
+        }
+    }
+
+    // Next output sample, or silence after the end
+    fn next_sample(&mut self) -> f32 {
+        if self.pending.is_empty() && !self.finished {
+            match self.stretcher.next_hop(&self.samples, self.speed) {
+                Some(hop) => self.pending.extend(hop),
+                None => self.finished = true,
+            }
+        }
+        self.pending.pop_front().unwrap_or(0.0)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished && self.pending.is_empty()
+    }
+}
+
+/// Playback shared between the audio thread and the speed controls
+pub type PlaybackHandle = Arc<Mutex<Playback>>;
+
+// Change the playback speed from lines typed on stdin until playback ends or `q` is typed
+fn speed_controls(playback: &PlaybackHandle, stopped: &AtomicBool) {
+    let (min, max) = stretch::SPEED_RANGE;
+    for line in std::io::stdin().lines() {
+        let Ok(line) = line else { break };
+        let mut playback = playback.lock().unwrap();
+        if playback.is_finished() {
+            break;
+        }
+        let speed = match line.trim() {
+            "q" => {
+                stopped.store(true, Ordering::SeqCst);
+                break;
+            }
+            "+" => playback.speed + SPEED_STEP,
+            "-" => playback.speed - SPEED_STEP,
+            other => match other.parse::<f64>() {
+                Ok(speed) => speed,
+                Err(_) => {
+                    println!("Type +, -, a speed from {} to {} or q", min, max);
+                    continue;
+                }
+            },
+        };
+        playback.speed = (speed.clamp(min, max) * 100.0).round() / 100.0;
+        let position = playback.stretcher.position() / playback.sample_rate as f64;
+        println!("Speed {:.2}x at {:.1}s", playback.speed, position);
+    }
+}
 fn write_input_data<T>(input: &[T], channels: ChannelCount, writer: &ClipHandle)
 where
     T: Sample,
@@ -276,28 +579,18 @@ where
     }
 }
 
-fn write_output_data<T>(output: &mut[T], channels: ChannelCount, writer: &ClipHandle)
+fn write_output_data<T>(output: &mut[T], channels: ChannelCount, playback: &PlaybackHandle)
 where
     T: Sample + FromSample<f32>,
 {
-    if let Ok(mut guard) = writer.try_lock() {
-        if let Some(clip) = guard.as_mut() {
-            for frame in output.chunks_mut(channels as usize) {
-                // Get the next sample from our recording (mono)
-                let next_sample = if clip.playback_position < clip.samples.len() {
-                    let sample_value = clip.samples[clip.playback_position];
-                    clip.playback_position += 1;
-                    sample_value
-                } else {
-                    // If we run out of samples, use silence
-                    0.0
-                };
-                
-                // Apply the same mono sample to all channels (typically left and right for stereo)
-                for sample in frame.iter_mut() {
-                    *sample = T::from_sample(next_sample);
-                }
-            }
+    let mut guard = playback.try_lock();
+    for frame in output.chunks_mut(channels as usize) {
+        // Play silence while the speed controls hold the lock and after the end
+        let next_sample = guard.as_mut().map_or(0.0, |playback| playback.next_sample());
+
+        // Apply the same mono sample to all channels (typically left and right for stereo)
+        for sample in frame.iter_mut() {
+            *sample = T::from_sample(next_sample);
         }
     }
 }
\ No newline at end of file
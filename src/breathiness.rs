@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+/// Spectral and cepstral measures of breathiness and brightness for voiced frames
+use rustfft::{Fft, FftPlanner};
+use rustfft::num_complex::Complex;
+use crate::dsp::{linear_fit, Spectrum};
+use crate::pitch::{MAX_F0, MIN_F0};
+
+/// Analysis window length in seconds; several periods so the harmonics are resolved
+const WINDOW: f64 = 0.04;
+/// Upper edge of the band used for the spectral tilt, in Hz
+const TILT_BAND: f32 = 5000.0;
+/// Frames averaged on each side when smoothing cepstra over time
+const TIME_SMOOTHING: usize = 3;
+/// Bins averaged on each side when smoothing a cepstrum over quefrency
+const QUEFRENCY_SMOOTHING: usize = 2;
+
+/// Breathiness related measures of one voiced frame
+#[derive(Debug, Clone, Copy)]
+pub struct SpectralFrame {
+    /// Level difference between the first and second harmonics in dB
+    pub h1_h2: f32,
+    /// Slope of the log spectrum up to 5 kHz in dB per kHz
+    pub tilt: f32,
+    /// Power weighted mean frequency of the spectrum up to 5 kHz in Hz
+    pub centroid: f32,
+}
+
+/// Computes spectra and cepstra of voiced frames at a fixed sample rate
+pub struct SpectralAnalyser {
+    sample_rate: f32,
+    len: usize,
+    spectrum: Spectrum,
+    ifft: Arc<dyn Fft<f32>>,
+}
+
+impl SpectralAnalyser {
+    pub fn new(sample_rate: u32) -> SpectralAnalyser {
+        let len = ((WINDOW * sample_rate as f64) as usize).next_power_of_two();
+        SpectralAnalyser {
+            sample_rate: sample_rate as f32,
+            len,
+            spectrum: Spectrum::new(len),
+            ifft: FftPlanner::new().plan_fft_inverse(len),
+        }
+    }
+
+    /// Measure the frame centred on sample `centre` with fundamental `f0`. Returns the measures and the frame's cepstrum
+    pub fn analyse(&mut self, samples: &[f32], centre: usize, f0: f32) -> Option<(SpectralFrame, Vec<f32>)> {
+        let start = centre.checked_sub(self.len / 2)?;
+        let frame = samples.get(start..start + self.len)?;
+        let levels: Vec<f32> = self.spectrum.magnitudes(frame).iter().map(|m| 20.0 * (m + 1e-9).log10()).collect();
+        let bin_hz = self.sample_rate / self.len as f32;
+
+        // Harmonic levels are the highest bins within 10% of the expected harmonic frequency
+        let harmonic = |n: f32| {
+            let low = ((n * f0 * 0.9) / bin_hz).floor() as usize;
+            let high = (((n * f0 * 1.1) / bin_hz).ceil() as usize).min(levels.len() - 1);
+            levels[low..=high].iter().copied().fold(f32::MIN, f32::max)
+        };
+        let h1_h2 = harmonic(1.0) - harmonic(2.0);
+
+        let band = (TILT_BAND / bin_hz) as usize;
+        let points: Vec<(f32, f32)> = (1..band.min(levels.len())).map(|bin| (bin as f32 * bin_hz / 1000.0, levels[bin])).collect();
+        let (tilt, _) = linear_fit(&points)?;
+        let power: Vec<(f32, f32)> = (1..band.min(levels.len())).map(|bin| (bin as f32 * bin_hz, 10f32.powf(levels[bin] / 10.0))).collect();
+        let total: f32 = power.iter().map(|p| p.1).sum();
+        let centroid = power.iter().map(|(frequency, p)| frequency * p).sum::<f32>() / total.max(f32::MIN_POSITIVE);
+
+        Some((SpectralFrame { h1_h2, tilt, centroid }, self.cepstrum(&levels)))
+    }
+
+    /// Smoothed cepstral peak prominence (in dB) of consecutive frames' cepstra, one value per frame
+    pub fn cpps(&self, cepstra: &[Vec<f32>]) -> Vec<Option<f32>> {
+        let low = (self.sample_rate / MAX_F0) as usize;
+        let high = ((self.sample_rate / MIN_F0) as usize).min(self.len / 2);
+        (0..cepstra.len())
+            .map(|i| {
+                let neighbours = &cepstra[i.saturating_sub(TIME_SMOOTHING)..(i + TIME_SMOOTHING + 1).min(cepstra.len())];
+                let averaged: Vec<f32> = (0..self.len / 2)
+                    .map(|q| neighbours.iter().map(|cepstrum| cepstrum[q]).sum::<f32>() / neighbours.len() as f32)
+                    .collect();
+                let smoothed: Vec<f32> = (0..averaged.len())
+                    .map(|q| {
+                        let around = &averaged[q.saturating_sub(QUEFRENCY_SMOOTHING)..(q + QUEFRENCY_SMOOTHING + 1).min(averaged.len())];
+                        around.iter().sum::<f32>() / around.len() as f32
+                    })
+                    .collect();
+
+                // Peak prominence above the regression line through the pitch quefrency range
+                let points: Vec<(f32, f32)> = (low..high).map(|q| (q as f32, smoothed[q])).collect();
+                let (slope, intercept) = linear_fit(&points)?;
+                let peak = (low..high).max_by(|&a, &b| smoothed[a].total_cmp(&smoothed[b]))?;
+                Some(smoothed[peak] - (slope * peak as f32 + intercept))
+            })
+            .collect()
+    }
+
+    // Power cepstrum in dB of a log spectrum (positive frequency half)
+    fn cepstrum(&self, levels: &[f32]) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = (0..self.len)
+            .map(|i| Complex::new(levels[if i < levels.len() { i } else { self.len - i }], 0.0))
+            .collect();
+        self.ifft.process(&mut buffer);
+        buffer[..self.len / 2].iter().map(|c| 20.0 * (c.norm() / self.len as f32 + 1e-9).log10()).collect()
+    }
+}
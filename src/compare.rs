@@ -0,0 +1,187 @@
+/// Time alignment of two clips with dynamic time warping, and the differences between aligned segments
+use color_eyre::eyre::{Result, eyre};
+use crate::analysis::{Analysis, HOP, Metric};
+use crate::audio_clips::{AudioClip, Speech};
+use crate::dsp::{self, Mfcc};
+
+/// Number of MFCCs compared per frame
+const COEFFICIENTS: usize = 12;
+/// Analysis window length in seconds, rounded up to a power of two samples
+const WINDOW: f64 = 0.025;
+/// Half width of the warping band around the diagonal, as a share of the longer clip
+const BAND: f64 = 0.2;
+
+/// A frame to frame alignment of two clips, with frames every `HOP` seconds
+#[derive(Debug, Clone)]
+pub struct Alignment {
+    path: Vec<(usize, usize)>,
+    /// Mean MFCC distance between aligned frames; lower values mean more similar clips
+    pub cost: f32,
+}
+
+impl Alignment {
+    /// Align the speech taken from two clips
+    pub fn of(a: &AudioClip, a_speech: &Speech, b: &AudioClip, b_speech: &Speech) -> Result<Alignment> {
+        let (a_features, b_features) = (features(a, a_speech), features(b, b_speech));
+        if a_features.is_empty() || b_features.is_empty() {
+            return Err(eyre!("Clips '{}' and '{}' are too short to align", a.name, b.name));
+        }
+        Ok(dtw(&a_features, &b_features))
+    }
+
+    /// The span of the second clip aligned with `start..end` seconds of the first, in seconds
+    pub fn map(&self, start: f64, end: f64) -> Option<(f64, f64)> {
+        let (first, last) = ((start / HOP) as usize, (end / HOP) as usize);
+        let matched = self.path.iter().filter(|(a, _)| (first..=last).contains(a)).map(|(_, b)| *b);
+        let (min, max) = matched.fold(None, |range: Option<(usize, usize)>, b| match range {
+            Some((min, max)) => Some((min.min(b), max.max(b))),
+            None => Some((b, b)),
+        })?;
+        Some((min as f64 * HOP, (max + 1) as f64 * HOP))
+    }
+}
+
+/// Differences between a speech segment of the first clip and the aligned audio of the second
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentComparison {
+    /// Start and end of the segment in the first clip, in seconds
+    pub a: (f64, f64),
+    /// Start and end of the aligned audio in the second clip, in seconds
+    pub b: (f64, f64),
+    /// Median pitch of the second minus the first, in semitones
+    pub pitch: Option<f32>,
+    /// Median first formant of the second minus the first, in Hz
+    pub f1: Option<f32>,
+    /// Median second formant of the second minus the first, in Hz
+    pub f2: Option<f32>,
+}
+
+/// Compare every speech segment of the first clip with the audio aligned to it in the second
+pub fn compare_segments(a: &AudioClip, a_speech: &Speech, a_analysis: &Analysis, b_analysis: &Analysis, alignment: &Alignment) -> Vec<SegmentComparison> {
+    let sample_rate = a.sample_rate as f64;
+    let mut comparisons = Vec::new();
+    for segment in &a_speech.segments {
+        let a_span = (segment.start as f64 / sample_rate, segment.end as f64 / sample_rate);
+        let Some(b_span) = alignment.map(a_span.0, a_span.1) else { continue };
+        let (a_frames, b_frames) = (a_analysis.between(a_span.0, a_span.1), b_analysis.between(b_span.0, b_span.1));
+        let difference = |metric: Metric| Some(b_frames.median(metric)? - a_frames.median(metric)?);
+        let pitch = match (a_frames.median(Metric::Pitch), b_frames.median(Metric::Pitch)) {
+            (Some(a_pitch), Some(b_pitch)) => Some(12.0 * (b_pitch / a_pitch).log2()),
+            _ => None,
+        };
+        comparisons.push(SegmentComparison { a: a_span, b: b_span, pitch, f1: difference(Metric::F1), f2: difference(Metric::F2) });
+    }
+    comparisons
+}
+
+// MFCCs of the clip's analysis samples every `HOP` seconds, with the clip's mean removed to cancel microphone differences
+fn features(audio_clip: &AudioClip, speech: &Speech) -> Vec<Vec<f32>> {
+    let len = ((WINDOW * audio_clip.sample_rate as f64) as usize).next_power_of_two();
+    let hop = (HOP * audio_clip.sample_rate as f64) as usize;
+    let mut mfcc = Mfcc::new(audio_clip.sample_rate, len, COEFFICIENTS);
+    let mut features: Vec<Vec<f32>> = dsp::frames(&speech.samples, len, hop).map(|(_, frame)| mfcc.compute(frame)).collect();
+    if !features.is_empty() {
+        let count = features.len() as f32;
+        let mean: Vec<f32> = (0..COEFFICIENTS).map(|k| features.iter().map(|frame| frame[k]).sum::<f32>() / count).collect();
+        for frame in features.iter_mut() {
+            for (value, mean) in frame.iter_mut().zip(&mean) {
+                *value -= mean;
+            }
+        }
+    }
+    features
+}
+
+// Dynamic time warping restricted to a band around the diagonal. Only the step directions are kept
+// for every cell, so long clips don't need a full cost matrix
+fn dtw(a: &[Vec<f32>], b: &[Vec<f32>]) -> Alignment {
+    const DIAGONAL: u8 = 0;
+    const UP: u8 = 1;
+    const LEFT: u8 = 2;
+    let (n, m) = (a.len(), b.len());
+    // Wide enough that consecutive rows overlap even when one clip is much longer
+    let width = (n.max(m) as f64 * BAND) as usize + m.div_ceil(n);
+    let band = |i: usize| {
+        let centre = i * (m - 1) / (n - 1).max(1);
+        (centre.saturating_sub(width), (centre + width).min(m - 1))
+    };
+    let distance = |i: usize, j: usize| a[i].iter().zip(&b[j]).map(|(x, y)| (x - y).powi(2)).sum::<f32>().sqrt();
+
+    let mut previous = vec![f32::INFINITY; m];
+    let mut current = vec![f32::INFINITY; m];
+    let mut steps: Vec<(usize, Vec<u8>)> = Vec::with_capacity(n);
+    for i in 0..n {
+        let (low, high) = band(i);
+        current.fill(f32::INFINITY);
+        let mut row = Vec::with_capacity(high - low + 1);
+        for j in low..=high {
+            let (best, step) = if i == 0 && j == 0 {
+                (0.0, DIAGONAL)
+            } else {
+                let diagonal = if i > 0 && j > 0 { previous[j - 1] } else { f32::INFINITY };
+                let up = if i > 0 { previous[j] } else { f32::INFINITY };
+                let left = if j > 0 { current[j - 1] } else { f32::INFINITY };
+                if diagonal <= up && diagonal <= left {
+                    (diagonal, DIAGONAL)
+                } else if up <= left {
+                    (up, UP)
+                } else {
+                    (left, LEFT)
+                }
+            };
+            current[j] = best + distance(i, j);
+            row.push(step);
+        }
+        steps.push((low, row));
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let total = previous[m - 1];
+    let (mut i, mut j) = (n - 1, m - 1);
+    let mut path = vec![(i, j)];
+    while i > 0 || j > 0 {
+        let (low, row) = &steps[i];
+        match row[j - low] {
+            DIAGONAL => { i -= 1; j -= 1; }
+            UP => i -= 1,
+            _ => j -= 1,
+        }
+        path.push((i, j));
+    }
+    path.reverse();
+    Alignment { cost: total / path.len() as f32, path }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(values: impl IntoIterator<Item = f32>) -> Vec<Vec<f32>> {
+        values.into_iter().map(|value| vec![value, value.sin()]).collect()
+    }
+
+    #[test]
+    fn identical_clips_align_on_the_diagonal() {
+        let a = frames((0..50).map(|i| i as f32 * 0.1));
+        let alignment = dtw(&a, &a);
+        assert_eq!(alignment.path, (0..50).map(|i| (i, i)).collect::<Vec<_>>());
+        assert_eq!(alignment.cost, 0.0);
+    }
+
+    #[test]
+    fn slower_clips_align_within_the_band() {
+        // `b` says the same thing at half the speed, so every frame of `a` matches two frames of `b`
+        let a = frames((0..40).map(|i| i as f32 * 0.1));
+        let b = frames((0..80).map(|i| (i / 2) as f32 * 0.1));
+        let alignment = dtw(&a, &b);
+        assert_eq!(alignment.path.first(), Some(&(0, 0)));
+        assert_eq!(alignment.path.last(), Some(&(39, 79)));
+        assert!(alignment.path.windows(2).all(|step| {
+            let ((i, j), (next_i, next_j)) = (step[0], step[1]);
+            next_i - i <= 1 && next_j - j <= 1 && (next_i, next_j) != (i, j)
+        }));
+        let width = (80.0 * BAND) as usize + 2;
+        assert!(alignment.path.iter().all(|&(i, j)| (j as isize - (i * 79 / 39) as isize).unsigned_abs() <= width));
+        assert_eq!(alignment.cost, 0.0);
+    }
+}
@@ -1,17 +1,100 @@
-use rusqlite::{params, Connection};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, ToSql};
 /// Raw mono audio clips
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Result, eyre};
 use crate::audio_clips::AudioClip;
+use crate::exercises::{Exercise, Step};
+use crate::glide::GlideScore;
+use crate::goals::Goal;
 use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
 use std::io::Cursor;
+use chrono::{DateTime, Duration, Utc};
 
 pub struct Db(Connection);
 
+/// Schema changes applied in order on top of the original table; entry `i` upgrades to `user_version` `i + 2`.
+/// An entry may hold several statements
+const MIGRATIONS: &[&str] = &[
+    "ALTER TABLE audio_clips ADD COLUMN deleted_at TEXT",
+    "ALTER TABLE audio_clips ADD COLUMN edits TEXT NOT NULL DEFAULT ''",
+    "CREATE TABLE goals (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT UNIQUE NOT NULL,
+        metric TEXT NOT NULL,
+        min REAL NOT NULL,
+        max REAL NOT NULL
+    )",
+    "CREATE TABLE clip_metrics (
+        clip_id INTEGER NOT NULL REFERENCES audio_clips(id) ON DELETE CASCADE,
+        metric TEXT NOT NULL,
+        value REAL,
+        PRIMARY KEY (clip_id, metric)
+    )",
+    "CREATE TABLE goal_scores (
+        clip_id INTEGER NOT NULL REFERENCES audio_clips(id) ON DELETE CASCADE,
+        goal_id INTEGER NOT NULL REFERENCES goals(id) ON DELETE CASCADE,
+        in_range REAL,
+        PRIMARY KEY (clip_id, goal_id)
+    )",
+    "ALTER TABLE audio_clips ADD COLUMN reference INTEGER NOT NULL DEFAULT 0",
+    r#"CREATE TABLE exercises (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT UNIQUE NOT NULL,
+        kind TEXT NOT NULL,
+        description TEXT NOT NULL
+    );
+    CREATE TABLE exercise_steps (
+        exercise_id INTEGER NOT NULL REFERENCES exercises(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        prompt TEXT NOT NULL,
+        duration REAL NOT NULL,
+        PRIMARY KEY (exercise_id, position)
+    );
+    ALTER TABLE audio_clips ADD COLUMN exercise TEXT;
+    INSERT INTO exercises (id, name, kind, description) VALUES
+        (1, 'sustained-vowel', 'vowel', 'Three sustained "ah" vowels at a comfortable pitch and loudness'),
+        (2, 'pitch-glides', 'glide', 'Smooth glides through your range, upwards and downwards'),
+        (3, 'sirens', 'glide', 'Sirens on "oo" from low to high and back in one breath'),
+        (4, 'rainbow-passage', 'reading', 'The first paragraph of the Rainbow Passage, read at a natural pace');
+    INSERT INTO exercise_steps (exercise_id, position, prompt, duration) VALUES
+        (1, 1, 'Take a comfortable breath and sustain "ah" as steadily as you can.', 5),
+        (1, 2, 'Again: sustain "ah" at the same pitch and loudness.', 5),
+        (1, 3, 'Once more: sustain "ah", keeping it steady to the end.', 5),
+        (2, 1, 'Glide on "ee" from your lowest comfortable note up to your highest.', 6),
+        (2, 2, 'Glide on "ee" from your highest comfortable note down to your lowest.', 6),
+        (3, 1, 'Siren on "oo": start low, slide up to the top of your range and back down.', 8),
+        (3, 2, 'Another siren on "oo", as smooth and even as you can.', 8),
+        (4, 1, 'Read aloud: When the sunlight strikes raindrops in the air, they act as a prism and form a rainbow. The rainbow is a division of white light into many beautiful colors. These take the shape of a long round arch, with its path high above, and its two ends apparently beyond the horizon. There is, according to legend, a boiling pot of gold at one end. People look, but no one ever finds it. When a man looks for something beyond his reach, his friends say he is looking for the pot of gold at the end of the rainbow.', 40);"#,
+    "CREATE TABLE glide_scores (
+        clip_id INTEGER PRIMARY KEY REFERENCES audio_clips(id) ON DELETE CASCADE,
+        start_f0 REAL NOT NULL,
+        end_f0 REAL NOT NULL,
+        min_f0 REAL NOT NULL,
+        max_f0 REAL NOT NULL,
+        coverage REAL NOT NULL,
+        smoothness REAL NOT NULL,
+        breaks INTEGER NOT NULL,
+        dropouts INTEGER NOT NULL
+    )",
+    "CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    )",
+];
+
+/// How long deleted clips stay in the trash before they are purged
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// A clip sitting in the trash
+pub struct TrashEntry {
+    pub name: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
 impl Db {
     pub fn open(path: &str) -> Result<Db> {
-        let conn = Connection::open(path)?;
+        let mut conn = Connection::open(path)?;
         conn.pragma_update(None, "page_size", 8192)?;
-        conn.pragma_update(None, "user_version", 1)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
         conn.execute(
             "CREATE TABLE IF NOT EXISTS audio_clips (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -23,96 +106,535 @@ impl Db {
             )",
             [],
         )?;
+        migrate(&mut conn, MIGRATIONS)?;
         Ok(Db(conn))
     }
 
-    pub fn save(&self, audio_clip: &mut AudioClip) -> Result<()> {
-      
+    /// Run `write` in a transaction, so either all of its changes are kept or, if it fails, none of them
+    pub fn transaction<T>(&self, write: impl FnOnce(&Db) -> Result<T>) -> Result<T> {
+        let transaction = self.0.unchecked_transaction()?;
+        let result = write(self)?;
+        transaction.commit()?;
+        Ok(result)
+    }
+
+    /// Insert a new clip, failing if a clip with the same name already exists
+    pub fn create(&self, audio_clip: &mut AudioClip) -> Result<()> {
+        self.ensure_name_free(&audio_clip.name)?;
+
         // Convert Vec<f32> to bytes
         let samples_blob = f32_vec_to_blob(&audio_clip.samples)?;
 
         self.0.execute(
-            "INSERT OR REPLACE INTO audio_clips (name, created_at, sample_rate, playback_position, samples) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO audio_clips (name, created_at, sample_rate, playback_position, samples, edits, reference, exercise) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
             params![
                 audio_clip.name,
                 audio_clip.created_at.to_string(),
                 audio_clip.sample_rate,
                 audio_clip.playback_position,
-                samples_blob
+                samples_blob,
+                audio_clip.edits.to_string(),
+                audio_clip.reference,
+                audio_clip.exercise,
             ],
         )?;
-        if audio_clip.id.is_none() {
-            audio_clip.id = Some(self.0.last_insert_rowid() as usize);
+        audio_clip.id = Some(self.0.last_insert_rowid() as usize);
+        Ok(())
+    }
+
+    /// Update a stored clip in place, keeping its id and only writing the columns that changed
+    pub fn update(&self, audio_clip: &AudioClip) -> Result<()> {
+        let id = audio_clip.id.ok_or(eyre!("Clip '{}' has not been saved yet", audio_clip.name))?;
+        let stored = self.load_by_id(id)?;
+
+        let mut columns: Vec<&str> = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if stored.name != audio_clip.name {
+            self.ensure_name_free(&audio_clip.name)?;
+            columns.push("name");
+            values.push(Box::new(audio_clip.name.clone()));
+        }
+        if stored.created_at != audio_clip.created_at {
+            columns.push("created_at");
+            values.push(Box::new(audio_clip.created_at.to_string()));
+        }
+        if stored.sample_rate != audio_clip.sample_rate {
+            columns.push("sample_rate");
+            values.push(Box::new(audio_clip.sample_rate));
+        }
+        if stored.playback_position != audio_clip.playback_position {
+            columns.push("playback_position");
+            values.push(Box::new(audio_clip.playback_position));
+        }
+        if stored.samples != audio_clip.samples {
+            columns.push("samples");
+            values.push(Box::new(f32_vec_to_blob(&audio_clip.samples)?));
+        }
+        if stored.edits != audio_clip.edits {
+            columns.push("edits");
+            values.push(Box::new(audio_clip.edits.to_string()));
+        }
+        if stored.reference != audio_clip.reference {
+            columns.push("reference");
+            values.push(Box::new(audio_clip.reference));
+        }
+        if stored.exercise != audio_clip.exercise {
+            columns.push("exercise");
+            values.push(Box::new(audio_clip.exercise.clone()));
+        }
+        if columns.is_empty() {
+            return Ok(());
+        }
+
+        let assignments: Vec<String> = columns.iter().map(|column| format!("{} = ?", column)).collect();
+        let sql = format!("UPDATE audio_clips SET {} WHERE id = ?", assignments.join(", "));
+        values.push(Box::new(id));
+        self.0.execute(&sql, params_from_iter(values.iter()))?;
+
+        // Cached analysis no longer matches the audio
+        if columns.iter().any(|column| matches!(*column, "samples" | "sample_rate" | "edits")) {
+            self.clear_analysis_cache(id)?;
         }
         Ok(())
     }
+
+    /// Fail if the name is taken. Names stay reserved while a clip sits in the trash so it can always be restored
+    pub fn ensure_name_free(&self, name: &str) -> Result<()> {
+        let deleted_at: Option<Option<String>> = self.0.query_row(
+            "SELECT deleted_at FROM audio_clips WHERE name = ?",
+            params![name],
+            |row| row.get(0),
+        ).optional()?;
+        match deleted_at {
+            None => Ok(()),
+            Some(None) => Err(eyre!("A clip named '{}' already exists", name)),
+            Some(Some(_)) => Err(eyre!("A clip named '{}' is in the trash (restore it or empty the trash first)", name)),
+        }
+    }
+
+    /// Check whether the clip with the given name is in the trash
+    pub fn in_trash(&self, name: &str) -> Result<bool> {
+        let in_trash = self.0.query_row(
+            "SELECT EXISTS(SELECT 1 FROM audio_clips WHERE name = ? AND deleted_at IS NOT NULL)",
+            params![name],
+            |row| row.get(0),
+        )?;
+        Ok(in_trash)
+    }
+
+    /// Check whether a clip with the given name is stored, including clips in the trash
+    pub fn exists(&self, name: &str) -> Result<bool> {
+        let exists = self.0.query_row(
+            "SELECT EXISTS(SELECT 1 FROM audio_clips WHERE name = ?)",
+            params![name],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Find a free name by appending `-2`, `-3`, ... to `name` until nothing conflicts
+    pub fn unique_name(&self, name: &str) -> Result<String> {
+        if !self.exists(name)? {
+            return Ok(name.to_string());
+        }
+        let mut suffix = 2;
+        loop {
+            let candidate = format!("{}-{}", name, suffix);
+            if !self.exists(&candidate)? {
+                return Ok(candidate);
+            }
+            suffix += 1;
+        }
+    }
+
+    pub fn load_by_id(&self, id: usize) -> Result<AudioClip> {
+        let audio_clip = self.0.query_row(
+            "SELECT * FROM audio_clips WHERE id = ?",
+            params![id],
+            row_to_audio_clip,
+        ).optional()?;
+        audio_clip.ok_or(eyre!("No clip with id {}", id))
+    }
+
     pub fn load(&self, name: &str) -> Result<AudioClip> {
-        let mut stmt = self.0.prepare("SELECT * FROM audio_clips WHERE name = ?")?;
-        let mut rows = stmt.query(params![name])?;
-        let audio_clip = rows.next()?.map(|row| {
-            let id: usize = row.get(0)?;
-            let name: String = row.get(1)?;
-            let created_at: String = row.get(2)?;
-            let sample_rate: u32 = row.get(3)?;
-            let playback_position: u32 = row.get(4)?;
-            let samples_blob: Vec<u8> = row.get(5)?;
-            let samples = blob_to_f32_vec(&samples_blob).unwrap();
-            let created_at = created_at.parse().unwrap();
-            Ok(AudioClip {
-                id: Some(id),
-                name,
-                created_at,
-                samples,
-                sample_rate,
-                playback_position: playback_position as usize,
-            })
-        }).unwrap_or_else(|| {
-            Err(rusqlite::Error::QueryReturnedNoRows)
-        })?;
-        
-        Ok(audio_clip)
-    }  
-    
+        let audio_clip = self.0.query_row(
+            "SELECT * FROM audio_clips WHERE name = ? AND deleted_at IS NULL",
+            params![name],
+            row_to_audio_clip,
+        ).optional()?;
+        audio_clip.ok_or(eyre!("No clip named '{}'", name))
+    }
+
     pub fn list(&self) -> Result<Vec<AudioClip>> {
-        let mut stmt = self.0.prepare("SELECT * FROM audio_clips ORDER BY created_at DESC")?;
-        let rows = stmt.query_map([], |row| {
-            let id: usize = row.get(0)?;
-            let name: String = row.get(1)?;
-            let created_at: String = row.get(2)?;
-            let sample_rate: u32 = row.get(3)?;
-            let playback_position: u32 = row.get(4)?;
-            let samples_blob: Vec<u8> = row.get(5)?;
-            
-            let samples = blob_to_f32_vec(&samples_blob).unwrap();
-            let created_at = created_at.parse().unwrap();
-            
-            Ok(AudioClip {
-                id: Some(id),
-                name,
-                created_at,
-                samples,
-                sample_rate,
-                playback_position: playback_position as usize,
-            })
-        })?;
-        
+        let mut stmt = self.0.prepare("SELECT * FROM audio_clips WHERE deleted_at IS NULL ORDER BY created_at DESC")?;
+        let rows = stmt.query_map([], row_to_audio_clip)?;
+
         let mut audio_clips = Vec::new();
         for clip in rows {
             audio_clips.push(clip?);
         }
-        
+
         Ok(audio_clips)
     }
 
+    /// Move a clip to the trash
     pub fn delete(&self, name: &str) -> Result<()> {
-        self.0.execute("DELETE FROM audio_clips WHERE name = ?", params![name])?;
+        let changed = self.0.execute(
+            "UPDATE audio_clips SET deleted_at = ? WHERE name = ? AND deleted_at IS NULL",
+            params![Utc::now().to_string(), name],
+        )?;
+        if changed == 0 {
+            return Err(eyre!("No clip named '{}'", name));
+        }
+        Ok(())
+    }
+
+    /// Give a clip a new name, keeping its id
+    pub fn rename(&self, name: &str, new_name: &str) -> Result<()> {
+        let mut audio_clip = self.load(name)?;
+        audio_clip.name = new_name.to_string();
+        self.update(&audio_clip)
+    }
+
+    /// Store a copy of a clip under a new name
+    pub fn duplicate(&self, name: &str, new_name: &str) -> Result<AudioClip> {
+        let mut audio_clip = self.load(name)?;
+        audio_clip.id = None;
+        audio_clip.name = new_name.to_string();
+        audio_clip.playback_position = 0;
+        self.create(&mut audio_clip)?;
+        Ok(audio_clip)
+    }
+
+    /// List the clips in the trash, most recently deleted first
+    pub fn trash_list(&self) -> Result<Vec<TrashEntry>> {
+        let mut stmt = self.0.prepare(
+            "SELECT name, deleted_at FROM audio_clips WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (name, deleted_at) = row?;
+            entries.push(TrashEntry { name, deleted_at: deleted_at.parse()? });
+        }
+        Ok(entries)
+    }
+
+    /// Take a clip back out of the trash
+    pub fn trash_restore(&self, name: &str) -> Result<()> {
+        let changed = self.0.execute(
+            "UPDATE audio_clips SET deleted_at = NULL WHERE name = ? AND deleted_at IS NOT NULL",
+            params![name],
+        )?;
+        if changed == 0 {
+            return Err(eyre!("No clip named '{}' in the trash", name));
+        }
+        Ok(())
+    }
+
+    /// Permanently remove trashed clips deleted before `before`, or all of them if `before` is None
+    pub fn trash_empty(&self, before: Option<DateTime<Utc>>) -> Result<usize> {
+        let mut removed = 0;
+        for entry in self.trash_list()? {
+            if before.is_none_or(|before| entry.deleted_at < before) {
+                removed += self.0.execute("DELETE FROM audio_clips WHERE name = ?", params![entry.name])?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Purge clips that have been in the trash longer than the retention period
+    pub fn purge_expired_trash(&self) -> Result<usize> {
+        self.trash_empty(Some(Utc::now() - Duration::days(TRASH_RETENTION_DAYS)))
+    }
+
+    pub fn create_goal(&self, goal: &mut Goal) -> Result<()> {
+        if self.load_goal(&goal.name).is_ok() {
+            return Err(eyre!("A goal named '{}' already exists", goal.name));
+        }
+        self.0.execute(
+            "INSERT INTO goals (name, metric, min, max) VALUES (?, ?, ?, ?)",
+            params![goal.name, goal.metric.name(), goal.min, goal.max],
+        )?;
+        goal.id = Some(self.0.last_insert_rowid() as usize);
+        Ok(())
+    }
+
+    pub fn load_goal(&self, name: &str) -> Result<Goal> {
+        let goal = self.0.query_row("SELECT * FROM goals WHERE name = ?", params![name], row_to_goal).optional()?;
+        goal.ok_or(eyre!("No goal named '{}'", name))
+    }
+
+    pub fn goals(&self) -> Result<Vec<Goal>> {
+        let mut stmt = self.0.prepare("SELECT * FROM goals ORDER BY name")?;
+        let rows = stmt.query_map([], row_to_goal)?;
+        let mut goals = Vec::new();
+        for goal in rows {
+            goals.push(goal?);
+        }
+        Ok(goals)
+    }
+
+    pub fn delete_goal(&self, name: &str) -> Result<()> {
+        let changed = self.0.execute("DELETE FROM goals WHERE name = ?", params![name])?;
+        if changed == 0 {
+            return Err(eyre!("No goal named '{}'", name));
+        }
+        Ok(())
+    }
+
+    pub fn create_exercise(&self, exercise: &mut Exercise) -> Result<()> {
+        if self.load_exercise(&exercise.name).is_ok() {
+            return Err(eyre!("An exercise named '{}' already exists", exercise.name));
+        }
+        self.0.execute(
+            "INSERT INTO exercises (name, kind, description) VALUES (?, ?, ?)",
+            params![exercise.name, exercise.kind.name(), exercise.description],
+        )?;
+        let id = self.0.last_insert_rowid() as usize;
+        for (index, step) in exercise.steps.iter().enumerate() {
+            self.0.execute(
+                "INSERT INTO exercise_steps (exercise_id, position, prompt, duration) VALUES (?, ?, ?, ?)",
+                params![id, index + 1, step.prompt, step.duration],
+            )?;
+        }
+        exercise.id = Some(id);
+        Ok(())
+    }
+
+    pub fn load_exercise(&self, name: &str) -> Result<Exercise> {
+        let exercise = self.0.query_row("SELECT * FROM exercises WHERE name = ?", params![name], row_to_exercise).optional()?;
+        let mut exercise = exercise.ok_or(eyre!("No exercise named '{}'", name))?;
+        exercise.steps = self.exercise_steps(exercise.id)?;
+        Ok(exercise)
+    }
+
+    pub fn exercises(&self) -> Result<Vec<Exercise>> {
+        let mut stmt = self.0.prepare("SELECT * FROM exercises ORDER BY name")?;
+        let rows = stmt.query_map([], row_to_exercise)?;
+        let mut exercises = Vec::new();
+        for exercise in rows {
+            let mut exercise = exercise?;
+            exercise.steps = self.exercise_steps(exercise.id)?;
+            exercises.push(exercise);
+        }
+        Ok(exercises)
+    }
+
+    pub fn delete_exercise(&self, name: &str) -> Result<()> {
+        let changed = self.0.execute("DELETE FROM exercises WHERE name = ?", params![name])?;
+        if changed == 0 {
+            return Err(eyre!("No exercise named '{}'", name));
+        }
         Ok(())
     }
+
+    // The steps of an exercise in order
+    fn exercise_steps(&self, exercise_id: Option<usize>) -> Result<Vec<Step>> {
+        let mut stmt = self.0.prepare("SELECT prompt, duration FROM exercise_steps WHERE exercise_id = ? ORDER BY position")?;
+        let rows = stmt.query_map(params![exercise_id], |row| Ok(Step { prompt: row.get("prompt")?, duration: row.get("duration")? }))?;
+        let mut steps = Vec::new();
+        for step in rows {
+            steps.push(step?);
+        }
+        Ok(steps)
+    }
+
+    /// The journal clips (not references) recorded before `before`, most recent first
+    pub fn clips_before(&self, before: DateTime<Utc>, limit: usize) -> Result<Vec<AudioClip>> {
+        let mut stmt = self.0.prepare(
+            "SELECT * FROM audio_clips WHERE deleted_at IS NULL AND reference = 0 AND created_at < ? ORDER BY created_at DESC LIMIT ?",
+        )?;
+        let rows = stmt.query_map(params![before.to_string(), limit], row_to_audio_clip)?;
+        let mut audio_clips = Vec::new();
+        for clip in rows {
+            audio_clips.push(clip?);
+        }
+        Ok(audio_clips)
+    }
+
+    /// A cached per-clip summary value. The outer option is None if nothing is cached
+    pub fn cached_metric(&self, clip_id: usize, metric: &str) -> Result<Option<Option<f64>>> {
+        let value = self.0.query_row(
+            "SELECT value FROM clip_metrics WHERE clip_id = ? AND metric = ?",
+            params![clip_id, metric],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(value)
+    }
+
+    pub fn cache_metric(&self, clip_id: usize, metric: &str, value: Option<f64>) -> Result<()> {
+        self.0.execute(
+            "INSERT OR REPLACE INTO clip_metrics (clip_id, metric, value) VALUES (?, ?, ?)",
+            params![clip_id, metric, value],
+        )?;
+        Ok(())
+    }
+
+    /// A cached goal score. The outer option is None if nothing is cached
+    pub fn cached_goal_score(&self, clip_id: usize, goal_id: usize) -> Result<Option<Option<f64>>> {
+        let score = self.0.query_row(
+            "SELECT in_range FROM goal_scores WHERE clip_id = ? AND goal_id = ?",
+            params![clip_id, goal_id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(score)
+    }
+
+    pub fn cache_goal_score(&self, clip_id: usize, goal_id: usize, in_range: Option<f64>) -> Result<()> {
+        self.0.execute(
+            "INSERT OR REPLACE INTO goal_scores (clip_id, goal_id, in_range) VALUES (?, ?, ?)",
+            params![clip_id, goal_id, in_range],
+        )?;
+        Ok(())
+    }
+
+    /// The stored glide score of a clip, if it has been scored
+    pub fn glide_score(&self, clip_id: usize) -> Result<Option<GlideScore>> {
+        let score = self.0.query_row(
+            "SELECT * FROM glide_scores WHERE clip_id = ?",
+            params![clip_id],
+            row_to_glide_score,
+        ).optional()?;
+        Ok(score)
+    }
+
+    pub fn save_glide_score(&self, clip_id: usize, score: &GlideScore) -> Result<()> {
+        self.0.execute(
+            "INSERT OR REPLACE INTO glide_scores (clip_id, start_f0, end_f0, min_f0, max_f0, coverage, smoothness, breaks, dropouts)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![clip_id, score.start, score.end, score.min, score.max, score.coverage, score.smoothness, score.breaks, score.dropouts],
+        )?;
+        Ok(())
+    }
+
+    /// Names and recording times of the clips with a stored glide score, with their scores, oldest first
+    pub fn glide_scores(&self) -> Result<Vec<(String, DateTime<Utc>, GlideScore)>> {
+        let mut stmt = self.0.prepare(
+            "SELECT audio_clips.name, audio_clips.created_at, glide_scores.* FROM glide_scores
+            JOIN audio_clips ON audio_clips.id = glide_scores.clip_id
+            WHERE audio_clips.deleted_at IS NULL
+            ORDER BY audio_clips.created_at",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let created_at: String = row.get("created_at")?;
+            let created_at = created_at.parse()
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e)))?;
+            Ok((row.get("name")?, created_at, row_to_glide_score(row)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// The stored value of a setting, if it has been set
+    pub fn setting(&self, key: &str) -> Result<Option<String>> {
+        let value = self.0.query_row("SELECT value FROM settings WHERE key = ?", params![key], |row| row.get(0)).optional()?;
+        Ok(value)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.0.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?)", params![key, value])?;
+        Ok(())
+    }
+
+    /// Remove a setting, returning whether it was set
+    pub fn delete_setting(&self, key: &str) -> Result<bool> {
+        let deleted = self.0.execute("DELETE FROM settings WHERE key = ?", params![key])?;
+        Ok(deleted > 0)
+    }
+
+    fn clear_analysis_cache(&self, clip_id: usize) -> Result<()> {
+        self.0.execute("DELETE FROM clip_metrics WHERE clip_id = ?", params![clip_id])?;
+        self.0.execute("DELETE FROM goal_scores WHERE clip_id = ?", params![clip_id])?;
+        self.0.execute("DELETE FROM glide_scores WHERE clip_id = ?", params![clip_id])?;
+        Ok(())
+    }
+}
+
+// Apply the migrations newer than the database's `user_version`. Each one runs in a transaction together with its
+// version bump, so a failing migration leaves the database at the previous version instead of half upgraded
+fn migrate(conn: &mut Connection, migrations: &[&str]) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in migrations.iter().enumerate().skip(version.saturating_sub(1)) {
+        let transaction = conn.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 2)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+// Helper function to build a Goal from a `goals` row
+fn row_to_goal(row: &Row) -> rusqlite::Result<Goal> {
+    let metric: String = row.get("metric")?;
+    let metric = metric.parse()
+        .map_err(|e: color_eyre::Report| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into()))?;
+    Ok(Goal {
+        id: Some(row.get("id")?),
+        name: row.get("name")?,
+        metric,
+        min: row.get("min")?,
+        max: row.get("max")?,
+    })
+}
+
+// Build a glide score from a `glide_scores` row
+fn row_to_glide_score(row: &Row) -> rusqlite::Result<GlideScore> {
+    Ok(GlideScore {
+        start: row.get("start_f0")?,
+        end: row.get("end_f0")?,
+        min: row.get("min_f0")?,
+        max: row.get("max_f0")?,
+        coverage: row.get("coverage")?,
+        smoothness: row.get("smoothness")?,
+        breaks: row.get("breaks")?,
+        dropouts: row.get("dropouts")?,
+    })
+}
+
+// Build an exercise from an `exercises` row, without its steps
+fn row_to_exercise(row: &Row) -> rusqlite::Result<Exercise> {
+    let kind: String = row.get("kind")?;
+    let kind = kind.parse()
+        .map_err(|e: color_eyre::Report| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into()))?;
+    Ok(Exercise {
+        id: Some(row.get("id")?),
+        name: row.get("name")?,
+        kind,
+        description: row.get("description")?,
+        steps: Vec::new(),
+    })
+}
+
+// Helper function to build an AudioClip from an `audio_clips` row
+fn row_to_audio_clip(row: &Row) -> rusqlite::Result<AudioClip> {
+    let samples_blob: Vec<u8> = row.get("samples")?;
+    let samples = blob_to_f32_vec(&samples_blob)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Blob, e.into()))?;
+    let created_at: String = row.get("created_at")?;
+    let created_at = created_at.parse()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?;
+    let playback_position: u32 = row.get("playback_position")?;
+    let edits: String = row.get("edits")?;
+    let edits = edits.parse()
+        .map_err(|e: color_eyre::Report| rusqlite::Error::FromSqlConversionFailure(7, rusqlite::types::Type::Text, e.into()))?;
+
+    Ok(AudioClip {
+        id: Some(row.get("id")?),
+        name: row.get("name")?,
+        created_at,
+        samples,
+        sample_rate: row.get("sample_rate")?,
+        playback_position: playback_position as usize,
+        edits,
+        reference: row.get("reference")?,
+        exercise: row.get("exercise")?,
+    })
 }
 
 // Helper function to convert Vec<f32> to a blob (Vec<u8>) for storage
 fn f32_vec_to_blob(samples: &[f32]) -> Result<Vec<u8>> {
-    let mut bytes = Vec::with_capacity(samples.len() * std::mem::size_of::<f32>());
+    let mut bytes = Vec::with_capacity(std::mem::size_of_val(samples));
     let mut cursor = Cursor::new(&mut bytes);
     
     for &sample in samples {
@@ -134,4 +656,176 @@ fn blob_to_f32_vec(blob: &[u8]) -> Result<Vec<f32>> {
     }
     
     Ok(samples)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edits::{Edit, EditList};
+    use crate::test_signals;
+
+    fn test_db() -> Db {
+        Db::open(":memory:").unwrap()
+    }
+
+    fn saved_clip(db: &Db, name: &str) -> AudioClip {
+        let mut audio_clip = test_signals::clip(test_signals::harmonic(150.0, 0.5, 16000), 16000);
+        audio_clip.name = name.to_string();
+        db.create(&mut audio_clip).unwrap();
+        audio_clip
+    }
+
+    fn user_version(conn: &Connection) -> usize {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn failed_migration_is_rolled_back() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let migrations = [
+            "CREATE TABLE a (x INTEGER)",
+            "CREATE TABLE b (x INTEGER); INSERT INTO missing VALUES (1)",
+        ];
+        assert!(migrate(&mut conn, &migrations).is_err());
+        assert_eq!(user_version(&conn), 2);
+        // The statement before the failing one is undone too, so the migration can run again once fixed
+        let tables: usize = conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'b'", [], |row| row.get(0)).unwrap();
+        assert_eq!(tables, 0);
+
+        let migrations = ["CREATE TABLE a (x INTEGER)", "CREATE TABLE b (x INTEGER)"];
+        migrate(&mut conn, &migrations).unwrap();
+        assert_eq!(user_version(&conn), 3);
+    }
+
+    #[test]
+    fn new_database_gets_every_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Db::open(dir.path().join("test.db").to_str().unwrap()).unwrap();
+        assert_eq!(user_version(&db.0), MIGRATIONS.len() + 1);
+        assert_eq!(db.exercises().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn edit_lists_round_trip_through_their_text() {
+        let text = "trim:0.5:-0.25;denoise-analysis:12:0.1:0.4;crop:0:1.5;denoise:6";
+        let edits: EditList = text.parse().unwrap();
+        assert_eq!(edits.0, vec![
+            Edit::Trim { start: 0.5, end: -0.25 },
+            Edit::Denoise { reduction: 12.0, noise: Some((0.1, 0.4)), analysis_only: true },
+            Edit::Crop { start: 0.0, end: 1.5 },
+            Edit::Denoise { reduction: 6.0, noise: None, analysis_only: false },
+        ]);
+        assert_eq!(edits.to_string(), text);
+        assert_eq!("".parse::<EditList>().unwrap(), EditList::default());
+        for bad in ["trim:1", "crop:a:2", "denoise:12:0.1", "fade:1:2", "trim:1:2;crop"] {
+            assert!(bad.parse::<EditList>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn crop_counts_from_the_trimmed_audio() {
+        // Three seconds; the trim leaves 0.5-2.5s and the crop then keeps 0.25-1.25s of that
+        let edits: EditList = "trim:0.5:-0.5;crop:0.25:1.25".parse().unwrap();
+        assert_eq!(edits.apply(48000, 16000).unwrap(), 12000..28000);
+
+        let edits: EditList = "trim:0.5:-0.5;denoise:12:0:0.25;crop:0.25:1.25".parse().unwrap();
+        let (range, reductions) = edits.resolve(48000, 16000).unwrap();
+        assert_eq!(range, 12000..28000);
+        assert_eq!(reductions[0].scope, 8000..40000);
+        assert_eq!(reductions[0].noise, Some(8000..12000));
+
+        assert!("trim:0.5:-0.5;crop:1:2.5".parse::<EditList>().unwrap().apply(48000, 16000).is_err());
+    }
+
+    #[test]
+    fn create_refuses_taken_names() {
+        let db = test_db();
+        let audio_clip = saved_clip(&db, "a");
+        assert!(audio_clip.id.is_some());
+        let mut copy = audio_clip.clone();
+        assert!(db.create(&mut copy).is_err());
+        assert_eq!(db.list().unwrap().len(), 1);
+        let loaded = db.load("a").unwrap();
+        assert_eq!((loaded.id, loaded.samples), (audio_clip.id, audio_clip.samples));
+    }
+
+    #[test]
+    fn update_writes_only_changed_columns() {
+        let db = test_db();
+        let mut audio_clip = saved_clip(&db, "a");
+        let id = audio_clip.id.unwrap();
+        // Log the columns named in each UPDATE's SET clause
+        db.0.execute_batch(
+            "CREATE TABLE updated (name TEXT);
+            CREATE TRIGGER updated_samples AFTER UPDATE OF samples ON audio_clips BEGIN INSERT INTO updated VALUES ('samples'); END;
+            CREATE TRIGGER updated_position AFTER UPDATE OF playback_position ON audio_clips BEGIN INSERT INTO updated VALUES ('playback_position'); END;
+            CREATE TRIGGER updated_edits AFTER UPDATE OF edits ON audio_clips BEGIN INSERT INTO updated VALUES ('edits'); END;",
+        ).unwrap();
+        let updated = || -> Vec<String> {
+            let mut stmt = db.0.prepare("SELECT name FROM updated").unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+        };
+        db.cache_metric(id, "pitch", Some(150.0)).unwrap();
+
+        db.update(&audio_clip).unwrap();
+        assert!(updated().is_empty());
+
+        audio_clip.playback_position = 100;
+        db.update(&audio_clip).unwrap();
+        assert_eq!(updated(), ["playback_position"]);
+        assert_eq!(db.cached_metric(id, "pitch").unwrap(), Some(Some(150.0)));
+
+        audio_clip.edits = "crop:0:0.25".parse().unwrap();
+        db.update(&audio_clip).unwrap();
+        assert_eq!(updated(), ["playback_position", "edits"]);
+        assert_eq!(db.cached_metric(id, "pitch").unwrap(), None);
+        assert_eq!(db.load("a").unwrap().edits, audio_clip.edits);
+
+        audio_clip.id = None;
+        assert!(db.update(&audio_clip).is_err());
+    }
+
+    #[test]
+    fn trashed_clips_keep_their_name_until_purged() {
+        let db = test_db();
+        saved_clip(&db, "a");
+        saved_clip(&db, "b");
+        db.delete("a").unwrap();
+        assert!(db.load("a").is_err());
+        assert!(db.delete("a").is_err());
+        assert_eq!(db.list().unwrap().len(), 1);
+        assert_eq!(db.trash_list().unwrap().iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["a"]);
+        let error = db.ensure_name_free("a").unwrap_err().to_string();
+        assert!(error.contains("in the trash"), "{}", error);
+        assert!(db.rename("b", "a").is_err());
+        assert_eq!(db.unique_name("a").unwrap(), "a-2");
+
+        db.trash_restore("a").unwrap();
+        assert!(db.load("a").is_ok());
+        assert!(db.trash_restore("a").is_err());
+        assert!(db.trash_list().unwrap().is_empty());
+
+        db.delete("a").unwrap();
+        db.delete("b").unwrap();
+        // Only clips deleted longer ago than the retention period are purged automatically
+        let expired = (Utc::now() - Duration::days(TRASH_RETENTION_DAYS + 1)).to_string();
+        db.0.execute("UPDATE audio_clips SET deleted_at = ? WHERE name = 'a'", params![expired]).unwrap();
+        assert_eq!(db.purge_expired_trash().unwrap(), 1);
+        assert_eq!(db.trash_list().unwrap().iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["b"]);
+        db.ensure_name_free("a").unwrap();
+        assert_eq!(db.trash_empty(None).unwrap(), 1);
+        db.ensure_name_free("b").unwrap();
+    }
+
+    #[test]
+    fn rename_keeps_the_id() {
+        let db = test_db();
+        let audio_clip = saved_clip(&db, "a");
+        saved_clip(&db, "b");
+        assert!(db.rename("a", "b").is_err());
+        db.rename("a", "c").unwrap();
+        assert_eq!(db.load("c").unwrap().id, audio_clip.id);
+        assert!(db.load("a").is_err());
+        db.ensure_name_free("a").unwrap();
+    }
+}
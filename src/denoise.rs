@@ -0,0 +1,140 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Reduction of steady background noise by Wiener filtering with a noise spectrum learned from the recording
+use rustfft::{Fft, FftPlanner};
+use rustfft::num_complex::Complex;
+use crate::dsp;
+use crate::vad::{self, VadConfig};
+
+/// Analysis frame length in seconds, rounded up to a power of two samples
+const FRAME: f64 = 0.032;
+/// Frames overlap by three quarters
+const OVERLAP: usize = 4;
+/// Silence this close to speech is left out of the noise profile, in seconds
+const SPEECH_MARGIN: f64 = 0.05;
+/// Weight of the previous frame in the a priori SNR estimate (decision-directed); higher values give less musical noise
+const SMOOTHING: f32 = 0.98;
+/// Noise reduction when none is given, in dB
+pub const DEFAULT_REDUCTION_DB: f64 = 12.0;
+/// Most noise reduction accepted, in dB
+pub const MAX_REDUCTION_DB: f64 = 40.0;
+
+/// Average noise power per frequency bin
+pub struct NoiseProfile {
+    stft: Stft,
+    power: Vec<f32>,
+}
+
+impl NoiseProfile {
+    /// Learn the noise from the given ranges of `samples`, or None if they hold less than one frame
+    pub fn learn(samples: &[f32], sample_rate: u32, regions: &[Range<usize>]) -> Option<NoiseProfile> {
+        let stft = Stft::new(sample_rate);
+        let mut power = vec![0.0; stft.len / 2 + 1];
+        let mut count = 0;
+        for region in regions {
+            for (_, frame) in dsp::frames(&samples[region.clone()], stft.len, stft.len / OVERLAP) {
+                for (total, bin) in power.iter_mut().zip(stft.forward(frame)) {
+                    *total += bin.norm_sqr();
+                }
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        for total in power.iter_mut() {
+            *total /= count as f32;
+        }
+        Some(NoiseProfile { stft, power })
+    }
+
+    /// Learn the noise from the silence around the speech in `samples`, or None if there is not enough of it
+    pub fn from_silence(samples: &[f32], sample_rate: u32) -> Option<NoiseProfile> {
+        let margin = (SPEECH_MARGIN * sample_rate as f64) as usize;
+        let mut regions = Vec::new();
+        let mut start = 0;
+        for segment in vad::speech_segments(samples, sample_rate, &VadConfig::default()) {
+            if segment.start > start + 2 * margin {
+                regions.push(start + margin..segment.start - margin);
+            }
+            start = segment.end;
+        }
+        if samples.len() > start + margin {
+            regions.push(start + margin..samples.len());
+        }
+        NoiseProfile::learn(samples, sample_rate, &regions)
+    }
+
+    /// `samples` with the noise turned down by up to `reduction_db`. Each bin is scaled by a Wiener gain from
+    /// its smoothed signal-to-noise ratio, which keeps the speech harmonics while avoiding musical noise
+    pub fn reduce(&self, samples: &[f32], reduction_db: f64) -> Vec<f32> {
+        let len = self.stft.len;
+        let hop = len / OVERLAP;
+        let floor = 10f32.powf(-reduction_db as f32 / 20.0);
+        // Pad so the first and last samples get full frames
+        let mut padded = vec![0.0; len];
+        padded.extend_from_slice(samples);
+        padded.resize(samples.len() + 2 * len, 0.0);
+
+        let mut output = vec![0.0f32; padded.len()];
+        let mut weights = vec![0.0f32; padded.len()];
+        let mut clean_power = vec![0.0f32; self.power.len()];
+        for (start, frame) in dsp::frames(&padded, len, hop) {
+            let mut spectrum = self.stft.forward(frame);
+            for (bin, value) in spectrum.iter_mut().enumerate().take(self.power.len()) {
+                let noise = self.power[bin].max(1e-12);
+                let power = value.norm_sqr();
+                let posterior = power / noise;
+                let prior = SMOOTHING * clean_power[bin] / noise + (1.0 - SMOOTHING) * (posterior - 1.0).max(0.0);
+                let gain = (prior / (1.0 + prior)).max(floor);
+                clean_power[bin] = gain * gain * power;
+                *value *= gain;
+            }
+            // Mirror the gains onto the negative frequencies so the result stays real
+            for bin in 1..len / 2 {
+                spectrum[len - bin] = spectrum[bin].conj();
+            }
+            for (i, sample) in self.stft.inverse(spectrum).into_iter().enumerate() {
+                output[start + i] += sample;
+                weights[start + i] += self.stft.window[i] * self.stft.window[i];
+            }
+        }
+        output.iter().zip(&weights).skip(len).take(samples.len())
+            .map(|(sample, weight)| if *weight > 1e-6 { sample / weight } else { 0.0 })
+            .collect()
+    }
+}
+
+// Short-time Fourier transform with a square root Hann window on both analysis and synthesis
+struct Stft {
+    len: usize,
+    window: Vec<f32>,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+}
+
+impl Stft {
+    fn new(sample_rate: u32) -> Stft {
+        let len = ((FRAME * sample_rate as f64) as usize).next_power_of_two();
+        let mut planner = FftPlanner::new();
+        Stft {
+            len,
+            window: dsp::hann(len).into_iter().map(f32::sqrt).collect(),
+            forward: planner.plan_fft_forward(len),
+            inverse: planner.plan_fft_inverse(len),
+        }
+    }
+
+    fn forward(&self, frame: &[f32]) -> Vec<Complex<f32>> {
+        let mut buffer: Vec<Complex<f32>> = frame.iter().zip(&self.window).map(|(sample, weight)| Complex::new(sample * weight, 0.0)).collect();
+        self.forward.process(&mut buffer);
+        buffer
+    }
+
+    // Windowed frame back from a full spectrum
+    fn inverse(&self, mut spectrum: Vec<Complex<f32>>) -> Vec<f32> {
+        self.inverse.process(&mut spectrum);
+        spectrum.iter().zip(&self.window).map(|(value, weight)| value.re / self.len as f32 * weight).collect()
+    }
+}
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+
+/// Shared signal processing helpers used by the analysers
+use rustfft::{Fft, FftPlanner};
+use rustfft::num_complex::Complex;
+
+/// Split `samples` into overlapping frames of `frame_len` samples every `hop` samples, yielding the start index of each frame
+pub fn frames(samples: &[f32], frame_len: usize, hop: usize) -> impl Iterator<Item = (usize, &[f32])> {
+    let count = if samples.len() < frame_len { 0 } else { (samples.len() - frame_len) / hop + 1 };
+    (0..count).map(move |i| (i * hop, &samples[i * hop..i * hop + frame_len]))
+}
+
+/// A Hann window of `len` samples
+pub fn hann(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / len as f32).cos())
+        .collect()
+}
+
+/// Root mean square level of a frame in dBFS
+pub fn rms_db(frame: &[f32]) -> f32 {
+    let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len().max(1) as f32;
+    10.0 * (power + 1e-12).log10()
+}
+
+/// Windowed magnitude spectra of fixed-size frames
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl Spectrum {
+    pub fn new(len: usize) -> Spectrum {
+        Spectrum {
+            fft: FftPlanner::new().plan_fft_forward(len),
+            window: hann(len),
+            buffer: vec![Complex::default(); len],
+        }
+    }
+
+    /// Magnitudes of the positive frequency bins (`len / 2 + 1` values) of a frame
+    pub fn magnitudes(&mut self, frame: &[f32]) -> Vec<f32> {
+        for (i, bin) in self.buffer.iter_mut().enumerate() {
+            *bin = Complex::new(frame.get(i).copied().unwrap_or(0.0) * self.window[i], 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+        self.buffer[..self.buffer.len() / 2 + 1].iter().map(|bin| bin.norm()).collect()
+    }
+}
+
+/// Mel frequency cepstral coefficients of fixed-size frames
+pub struct Mfcc {
+    spectrum: Spectrum,
+    // Triangular mel filters as (first bin, weights)
+    filters: Vec<(usize, Vec<f32>)>,
+    coefficients: usize,
+}
+
+impl Mfcc {
+    const FILTERS: usize = 26;
+    const MAX_FREQUENCY: f32 = 8000.0;
+
+    /// Computes `coefficients` MFCCs (excluding the energy term c0) of `len` sample frames
+    pub fn new(sample_rate: u32, len: usize, coefficients: usize) -> Mfcc {
+        let mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+        let hz = |mel: f32| 700.0 * (10f32.powf(mel / 2595.0) - 1.0);
+        let top = mel(Self::MAX_FREQUENCY.min(sample_rate as f32 / 2.0));
+        let bin_hz = sample_rate as f32 / len as f32;
+        let edges: Vec<f32> = (0..Self::FILTERS + 2).map(|i| hz(top * i as f32 / (Self::FILTERS + 1) as f32) / bin_hz).collect();
+        let filters = edges.windows(3)
+            .map(|edge| {
+                let first = edge[0].ceil() as usize;
+                let weights = (first..=edge[2].floor() as usize)
+                    .map(|bin| {
+                        let bin = bin as f32;
+                        if bin <= edge[1] { (bin - edge[0]) / (edge[1] - edge[0]) } else { (edge[2] - bin) / (edge[2] - edge[1]) }
+                    })
+                    .collect();
+                (first, weights)
+            })
+            .collect();
+        Mfcc { spectrum: Spectrum::new(len), filters, coefficients }
+    }
+
+    pub fn compute(&mut self, frame: &[f32]) -> Vec<f32> {
+        let magnitudes = self.spectrum.magnitudes(frame);
+        let energies: Vec<f32> = self.filters.iter()
+            .map(|(first, weights)| {
+                let energy: f32 = weights.iter().enumerate().map(|(i, weight)| weight * magnitudes[first + i].powi(2)).sum();
+                (energy + 1e-10).ln()
+            })
+            .collect();
+        // DCT-II of the log filter energies
+        let count = energies.len() as f32;
+        (1..=self.coefficients)
+            .map(|k| {
+                energies.iter().enumerate()
+                    .map(|(n, energy)| energy * (std::f32::consts::PI * k as f32 * (n as f32 + 0.5) / count).cos())
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// A second order IIR filter section (Direct Form I)
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// A filter from its numerator `b` and denominator `a` coefficients, normalised by `a[0]`
+    pub fn new(b: [f64; 3], a: [f64; 3]) -> Biquad {
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    /// High pass removing frequencies below `freq`
+    pub fn high_pass(sample_rate: f64, freq: f64, q: f64) -> Biquad {
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        Biquad::new(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Low pass removing frequencies above `freq`
+    pub fn low_pass(sample_rate: f64, freq: f64, q: f64) -> Biquad {
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        Biquad::new(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Peaking equaliser boosting (or cutting) frequencies around `freq` by `gain_db`
+    pub fn peaking(sample_rate: f64, freq: f64, q: f64, gain_db: f64) -> Biquad {
+        let a = 10f64.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f64::consts::PI * freq / sample_rate;
+        let (cos, alpha) = (w0.cos(), w0.sin() / (2.0 * q));
+        Biquad::new(
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        )
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let x = input as f64;
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y as f32
+    }
+}
+
+/// Low pass filter and keep every `factor`th sample
+pub fn decimate(samples: &[f32], factor: usize) -> Vec<f32> {
+    if factor <= 1 {
+        return samples.to_vec();
+    }
+    // Windowed sinc low pass just below the new Nyquist frequency
+    const TAPS: usize = 63;
+    let cutoff = 0.9 / factor as f32;
+    let half = (TAPS / 2) as isize;
+    let taps: Vec<f32> = (-half..=half)
+        .map(|k| {
+            let x = k as f32;
+            let sinc = if k == 0 { cutoff } else { (std::f32::consts::PI * cutoff * x).sin() / (std::f32::consts::PI * x) };
+            let window = 0.54 + 0.46 * (std::f32::consts::PI * x / half as f32).cos();
+            sinc * window
+        })
+        .collect();
+    (0..samples.len())
+        .step_by(factor)
+        .map(|i| {
+            taps.iter().enumerate()
+                .filter_map(|(j, tap)| {
+                    let index = i as isize + j as isize - half;
+                    samples.get(usize::try_from(index).ok()?).map(|s| s * tap)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Read `samples` `ratio` times as fast (raising all frequencies by `ratio`) with windowed sinc interpolation,
+/// low pass filtering first when reading faster so nothing aliases
+pub fn resample_by(samples: &[f32], ratio: f64) -> Vec<f32> {
+    const HALF_TAPS: isize = 32;
+    let cutoff = (1.0 / ratio).min(1.0);
+    let len = (samples.len() as f64 / ratio) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let centre = position.floor() as isize;
+            (centre - HALF_TAPS + 1..=centre + HALF_TAPS)
+                .filter_map(|index| {
+                    let sample = *samples.get(usize::try_from(index).ok()?)?;
+                    let x = position - index as f64;
+                    let sinc = if x.abs() < 1e-9 { cutoff } else { (std::f64::consts::PI * cutoff * x).sin() / (std::f64::consts::PI * x) };
+                    let window = 0.5 + 0.5 * (std::f64::consts::PI * x / HALF_TAPS as f64).cos();
+                    Some(sample as f64 * sinc * window)
+                })
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+/// Linear prediction coefficients `[1, a1, .., a_order]` of a frame (autocorrelation method)
+pub fn lpc(frame: &[f32], order: usize) -> Option<Vec<f64>> {
+    let autocorrelation: Vec<f64> = (0..=order)
+        .map(|lag| frame.iter().zip(&frame[lag.min(frame.len())..]).map(|(a, b)| *a as f64 * *b as f64).sum())
+        .collect();
+    if autocorrelation[0] <= f64::EPSILON {
+        return None;
+    }
+
+    // Levinson-Durbin recursion
+    let mut coefficients = vec![0.0; order + 1];
+    coefficients[0] = 1.0;
+    let mut error = autocorrelation[0];
+    for i in 1..=order {
+        let acc: f64 = (0..i).map(|j| coefficients[j] * autocorrelation[i - j]).sum();
+        let reflection = -acc / error;
+        let previous = coefficients.clone();
+        for j in 1..i {
+            coefficients[j] = previous[j] + reflection * previous[i - j];
+        }
+        coefficients[i] = reflection;
+        error *= 1.0 - reflection * reflection;
+        if error <= 0.0 {
+            return None;
+        }
+    }
+    Some(coefficients)
+}
+
+/// Roots of the polynomial `coefficients[0] * z^n + coefficients[1] * z^(n-1) + ..` (Durand-Kerner iteration)
+pub fn polynomial_roots(coefficients: &[f64]) -> Vec<Complex<f64>> {
+    let degree = coefficients.len().saturating_sub(1);
+    if degree == 0 {
+        return Vec::new();
+    }
+    let monic: Vec<f64> = coefficients.iter().map(|c| c / coefficients[0]).collect();
+    let evaluate = |z: Complex<f64>| monic.iter().fold(Complex::new(0.0, 0.0), |acc, &c| acc * z + c);
+
+    let seed = Complex::new(0.4, 0.9);
+    let mut roots: Vec<Complex<f64>> = (0..degree).map(|i| seed.powu(i as u32)).collect();
+    for _ in 0..500 {
+        let mut largest_step: f64 = 0.0;
+        for i in 0..degree {
+            let denominator = (0..degree)
+                .filter(|&j| j != i)
+                .fold(Complex::new(1.0, 0.0), |acc, j| acc * (roots[i] - roots[j]));
+            if denominator.norm() < f64::EPSILON {
+                continue;
+            }
+            let step = evaluate(roots[i]) / denominator;
+            roots[i] -= step;
+            largest_step = largest_step.max(step.norm());
+        }
+        if largest_step < 1e-10 {
+            break;
+        }
+    }
+    roots
+}
+
+/// Least squares line through points, as (slope, intercept)
+pub fn linear_fit(points: &[(f32, f32)]) -> Option<(f32, f32)> {
+    let n = points.len() as f32;
+    if n < 2.0 {
+        return None;
+    }
+    let mean_x = points.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f32>() / n;
+    let covariance: f32 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let variance: f32 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    if variance <= 0.0 {
+        return None;
+    }
+    let slope = covariance / variance;
+    Some((slope, mean_y - slope * mean_x))
+}
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Non-destructive edits stored alongside the original samples of a clip
+use color_eyre::eyre::{Report, Result, eyre};
+
+/// A single edit operation. Times are in seconds relative to the audio produced by the previous edits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    /// Cut `start` seconds off the beginning and end the clip at `end`; a negative or zero `end` counts back from the end
+    Trim { start: f64, end: f64 },
+    /// Keep only the audio between `start` and `end`
+    Crop { start: f64, end: f64 },
+    /// Turn steady background noise down by up to `reduction` dB, learning the noise from the `noise` region
+    /// (start and end in seconds) or else from the silence between the speech. With `analysis_only` only the
+    /// audio the analysers look at is cleaned up
+    Denoise { reduction: f64, noise: Option<(f64, f64)>, analysis_only: bool },
+}
+
+impl Edit {
+    // Resolve this edit to the sub-range it keeps of a `len` samples long signal
+    fn keep(&self, len: usize, sample_rate: u32) -> Result<Range<usize>> {
+        let duration = len as f64 / sample_rate as f64;
+        let (start, end) = match *self {
+            Edit::Trim { start, end } => (start, if end <= 0.0 { duration + end } else { end }),
+            Edit::Crop { start, end } => (start, end),
+            Edit::Denoise { noise, .. } => {
+                if let Some((start, end)) = noise {
+                    Edit::Crop { start, end }.keep(len, sample_rate)
+                        .map_err(|_| eyre!("The noise region of edit {} is outside the clip (0.000-{:.3}s)", self, duration))?;
+                }
+                return Ok(0..len);
+            }
+        };
+        if start < 0.0 || end > duration + f64::EPSILON || start >= end {
+            return Err(eyre!("Edit {} is outside the clip (0.000-{:.3}s)", self, duration));
+        }
+        let to_index = |seconds: f64| ((seconds * sample_rate as f64).round() as usize).min(len);
+        Ok(to_index(start)..to_index(end))
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edit::Trim { start, end } => write!(f, "trim:{}:{}", start, end),
+            Edit::Crop { start, end } => write!(f, "crop:{}:{}", start, end),
+            Edit::Denoise { reduction, noise, analysis_only } => {
+                write!(f, "{}:{}", if *analysis_only { "denoise-analysis" } else { "denoise" }, reduction)?;
+                match noise {
+                    Some((start, end)) => write!(f, ":{}:{}", start, end),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Edit {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Edit> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let denoise = |reduction: &str, noise: Option<(&str, &str)>| -> Result<Edit> {
+            Ok(Edit::Denoise {
+                reduction: reduction.parse()?,
+                noise: noise.map(|(start, end)| Ok::<_, Report>((start.parse()?, end.parse()?))).transpose()?,
+                analysis_only: parts[0] == "denoise-analysis",
+            })
+        };
+        match (parts[0], &parts[1..]) {
+            ("trim", [start, end]) => Ok(Edit::Trim { start: start.parse()?, end: end.parse()? }),
+            ("crop", [start, end]) => Ok(Edit::Crop { start: start.parse()?, end: end.parse()? }),
+            ("denoise" | "denoise-analysis", [reduction]) => denoise(reduction, None),
+            ("denoise" | "denoise-analysis", [reduction, start, end]) => denoise(reduction, Some((start, end))),
+            ("trim" | "crop" | "denoise" | "denoise-analysis", _) => Err(eyre!("Invalid edit '{}'", s)),
+            (kind, _) => Err(eyre!("Unknown edit '{}'", kind)),
+        }
+    }
+}
+
+/// A noise reduction edit resolved to positions in the original samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseReduction {
+    /// Most the noise is turned down, in dB
+    pub reduction: f64,
+    pub analysis_only: bool,
+    /// The original samples the edit was added on
+    pub scope: Range<usize>,
+    /// The original samples holding only noise, or None to learn the noise from the silence in `scope`
+    pub noise: Option<Range<usize>>,
+}
+
+/// The ordered edits applied on top of a clip's original samples
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EditList(pub Vec<Edit>);
+
+impl EditList {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The range of the original samples left after applying every edit
+    pub fn apply(&self, len: usize, sample_rate: u32) -> Result<Range<usize>> {
+        Ok(self.resolve(len, sample_rate)?.0)
+    }
+
+    /// The range of the original samples left after applying every edit, with the noise reductions in the order
+    /// they were added
+    pub fn resolve(&self, len: usize, sample_rate: u32) -> Result<(Range<usize>, Vec<NoiseReduction>)> {
+        let mut range = 0..len;
+        let mut reductions = Vec::new();
+        for edit in &self.0 {
+            let keep = edit.keep(range.len(), sample_rate)?;
+            if let Edit::Denoise { reduction, noise, analysis_only } = *edit {
+                let noise = noise
+                    .map(|(start, end)| Edit::Crop { start, end }.keep(range.len(), sample_rate))
+                    .transpose()?
+                    .map(|noise| range.start + noise.start..range.start + noise.end);
+                reductions.push(NoiseReduction { reduction, analysis_only, scope: range.clone(), noise });
+            }
+            range = range.start + keep.start..range.start + keep.end;
+        }
+        Ok((range, reductions))
+    }
+}
+
+impl fmt::Display for EditList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let edits: Vec<String> = self.0.iter().map(|edit| edit.to_string()).collect();
+        write!(f, "{}", edits.join(";"))
+    }
+}
+
+impl FromStr for EditList {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<EditList> {
+        let edits = s.split(';')
+            .filter(|edit| !edit.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Edit>>>()?;
+        Ok(EditList(edits))
+    }
+}
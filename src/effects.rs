@@ -0,0 +1,325 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Effect chains applied to clips before playback, export and rendering
+use color_eyre::eyre::{Report, Result, eyre};
+use crate::dsp::Biquad;
+use crate::loudness::Loudness;
+use crate::shift::{self, Semitones};
+
+/// Settings key of the default effect chain
+pub const FX_SETTING: &str = "fx";
+/// Number of samples processed at a time
+const BLOCK: usize = 1024;
+/// Q of the high and low pass filters (Butterworth)
+const FILTER_Q: f64 = std::f64::consts::FRAC_1_SQRT_2;
+/// Q of an equaliser band when none is given
+const DEFAULT_EQ_Q: f64 = 1.0;
+/// Compression ratio when none is given
+const DEFAULT_RATIO: f64 = 4.0;
+/// How quickly the compressor reacts to rising and falling levels, in seconds
+const ATTACK: f64 = 0.005;
+const RELEASE: f64 = 0.1;
+
+/// Audio processing applied block by block
+pub trait Effect {
+    /// Look at the whole signal before it is processed, for effects that depend on what comes later
+    fn prepare(&mut self, _samples: &[f32]) {}
+
+    /// Process the next block of samples in place
+    fn process(&mut self, block: &mut [f32]);
+}
+
+/// One effect of a chain as written in a chain description, e.g. `hpf:80` or `gain:+3db`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectSpec {
+    /// `gain:<dB>`
+    Gain { db: f64 },
+    /// `hpf:<Hz>`
+    HighPass { freq: f64 },
+    /// `lpf:<Hz>`
+    LowPass { freq: f64 },
+    /// `eq:<Hz>:<dB>[:<Q>]`, a peaking equaliser band
+    Eq { freq: f64, gain_db: f64, q: f64 },
+    /// `comp:<threshold dB>[:<ratio>]`
+    Compressor { threshold: f64, ratio: f64 },
+    /// `pitch:<semitones>[:keep]`, moving the formants along unless they are kept
+    Pitch { semitones: Semitones, keep_formants: bool },
+    /// `formants:<ratio>`
+    Formants { ratio: f64 },
+    /// `norm:<LUFS>`, normalising the integrated loudness
+    Normalize { target: f64 },
+}
+
+/// Descriptions of the available effects, shown by `fx list`
+pub const EFFECT_HELP: [(&str, &str); 8] = [
+    ("gain:<dB>", "Change the level, e.g. gain:+3db"),
+    ("hpf:<Hz>", "High pass filter, e.g. hpf:80"),
+    ("lpf:<Hz>", "Low pass filter, e.g. lpf:8000"),
+    ("eq:<Hz>:<dB>[:<Q>]", "Boost or cut a band, e.g. eq:3000:-4db:2"),
+    ("comp:<dB>[:<ratio>]", "Compress levels above a threshold, e.g. comp:-20db:4"),
+    ("pitch:<semitones>[:keep]", "Shift the pitch, keeping the formants with :keep, e.g. pitch:+3st"),
+    ("formants:<ratio>", "Shift the formants without changing the pitch, e.g. formants:1.1"),
+    ("norm:<LUFS>", "Normalise the integrated loudness, e.g. norm:-23"),
+];
+
+impl EffectSpec {
+    /// A processor for audio at `sample_rate`
+    pub fn build(&self, sample_rate: u32) -> Result<Box<dyn Effect>> {
+        let rate = sample_rate as f64;
+        let check_freq = |freq: f64| {
+            if freq <= 0.0 || freq >= rate / 2.0 {
+                return Err(eyre!("Effect '{}' needs a frequency between 0 and {} Hz", self, rate / 2.0));
+            }
+            Ok(())
+        };
+        Ok(match *self {
+            EffectSpec::Gain { db } => Box::new(Gain(10f32.powf(db as f32 / 20.0))),
+            EffectSpec::HighPass { freq } => {
+                check_freq(freq)?;
+                Box::new(Filter(Biquad::high_pass(rate, freq, FILTER_Q)))
+            }
+            EffectSpec::LowPass { freq } => {
+                check_freq(freq)?;
+                Box::new(Filter(Biquad::low_pass(rate, freq, FILTER_Q)))
+            }
+            EffectSpec::Eq { freq, gain_db, q } => {
+                check_freq(freq)?;
+                Box::new(Filter(Biquad::peaking(rate, freq, q, gain_db)))
+            }
+            EffectSpec::Compressor { threshold, ratio } => Box::new(Compressor {
+                threshold: threshold as f32,
+                slope: (1.0 - 1.0 / ratio) as f32,
+                attack: (-1.0 / (ATTACK * rate)).exp() as f32,
+                release: (-1.0 / (RELEASE * rate)).exp() as f32,
+                envelope: 0.0,
+            }),
+            EffectSpec::Pitch { semitones, keep_formants } => {
+                let formant_ratio = if keep_formants { 1.0 } else { semitones.ratio() };
+                Box::new(Shift::new(sample_rate, semitones.ratio(), formant_ratio))
+            }
+            EffectSpec::Formants { ratio } => Box::new(Shift::new(sample_rate, 1.0, ratio)),
+            EffectSpec::Normalize { target } => Box::new(Normalize { target, sample_rate, gain: 1.0 }),
+        })
+    }
+}
+
+impl fmt::Display for EffectSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EffectSpec::Gain { db } => write!(f, "gain:{:+}db", db),
+            EffectSpec::HighPass { freq } => write!(f, "hpf:{}", freq),
+            EffectSpec::LowPass { freq } => write!(f, "lpf:{}", freq),
+            EffectSpec::Eq { freq, gain_db, q } => write!(f, "eq:{}:{:+}db:{}", freq, gain_db, q),
+            EffectSpec::Compressor { threshold, ratio } => write!(f, "comp:{}db:{}", threshold, ratio),
+            EffectSpec::Pitch { semitones, keep_formants: true } => write!(f, "pitch:{}:keep", semitones),
+            EffectSpec::Pitch { semitones, keep_formants: false } => write!(f, "pitch:{}", semitones),
+            EffectSpec::Formants { ratio } => write!(f, "formants:{}", ratio),
+            EffectSpec::Normalize { target } => write!(f, "norm:{}", target),
+        }
+    }
+}
+
+impl FromStr for EffectSpec {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<EffectSpec> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let number = |index: usize, unit: &str| -> Result<f64> {
+            let value = parts.get(index).ok_or(eyre!("Effect '{}' is missing a value", s))?;
+            let lower = value.to_lowercase();
+            lower.strip_suffix(unit).unwrap_or(&lower).parse().map_err(|_| eyre!("Invalid value '{}' in effect '{}'", value, s))
+        };
+        let optional = |index: usize, unit: &str, default: f64| if parts.len() > index { number(index, unit) } else { Ok(default) };
+        let spec = match (parts[0], parts.len()) {
+            ("gain", 2) => EffectSpec::Gain { db: number(1, "db")? },
+            ("hpf", 2) => EffectSpec::HighPass { freq: number(1, "hz")? },
+            ("lpf", 2) => EffectSpec::LowPass { freq: number(1, "hz")? },
+            ("eq", 3 | 4) => EffectSpec::Eq { freq: number(1, "hz")?, gain_db: number(2, "db")?, q: optional(3, "", DEFAULT_EQ_Q)? },
+            ("comp", 2 | 3) => EffectSpec::Compressor { threshold: number(1, "db")?, ratio: optional(2, "", DEFAULT_RATIO)? },
+            ("pitch", 2) => EffectSpec::Pitch { semitones: parts[1].parse()?, keep_formants: false },
+            ("pitch", 3) if parts[2] == "keep" => EffectSpec::Pitch { semitones: parts[1].parse()?, keep_formants: true },
+            ("formants", 2) => EffectSpec::Formants { ratio: number(1, "")? },
+            ("norm", 2) => EffectSpec::Normalize { target: number(1, "lufs")? },
+            _ => return Err(eyre!("Unknown effect '{}' (see `oxygen fx list`)", s)),
+        };
+        match spec {
+            EffectSpec::Eq { q, .. } if q <= 0.0 => Err(eyre!("The Q of '{}' must be positive", s)),
+            EffectSpec::Compressor { ratio, .. } if ratio < 1.0 => Err(eyre!("The ratio of '{}' must be at least 1", s)),
+            EffectSpec::Formants { ratio } if !(shift::FORMANT_RATIO_RANGE.0..=shift::FORMANT_RATIO_RANGE.1).contains(&ratio) => {
+                Err(eyre!("Formant ratio {} is outside {}-{}", ratio, shift::FORMANT_RATIO_RANGE.0, shift::FORMANT_RATIO_RANGE.1))
+            }
+            spec => Ok(spec),
+        }
+    }
+}
+
+/// Effects applied one after another, written as a comma separated list such as `hpf:80,gain:+3db`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chain(pub Vec<EffectSpec>);
+
+impl Chain {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Run the chain over `samples` in place, each effect in blocks of `BLOCK` samples
+    pub fn apply(&self, samples: &mut [f32], sample_rate: u32) -> Result<()> {
+        for spec in &self.0 {
+            let mut effect = spec.build(sample_rate)?;
+            effect.prepare(samples);
+            for block in samples.chunks_mut(BLOCK) {
+                effect.process(block);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let specs: Vec<String> = self.0.iter().map(|spec| spec.to_string()).collect();
+        write!(f, "{}", specs.join(","))
+    }
+}
+
+impl FromStr for Chain {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Chain> {
+        let specs = s.split(',').filter(|spec| !spec.trim().is_empty()).map(str::parse).collect::<Result<_>>()?;
+        Ok(Chain(specs))
+    }
+}
+
+struct Gain(f32);
+
+impl Effect for Gain {
+    fn process(&mut self, block: &mut [f32]) {
+        for sample in block {
+            *sample *= self.0;
+        }
+    }
+}
+
+struct Filter(Biquad);
+
+impl Effect for Filter {
+    fn process(&mut self, block: &mut [f32]) {
+        for sample in block {
+            *sample = self.0.process(*sample);
+        }
+    }
+}
+
+// Feed forward compressor following the peak level with separate attack and release times
+struct Compressor {
+    threshold: f32,
+    /// Fraction of the level above the threshold that is taken off
+    slope: f32,
+    attack: f32,
+    release: f32,
+    envelope: f32,
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, block: &mut [f32]) {
+        for sample in block {
+            let level = sample.abs();
+            let coefficient = if level > self.envelope { self.attack } else { self.release };
+            self.envelope = coefficient * self.envelope + (1.0 - coefficient) * level;
+            let over = 20.0 * (self.envelope + 1e-9).log10() - self.threshold;
+            if over > 0.0 {
+                *sample *= 10f32.powf(-over * self.slope / 20.0);
+            }
+        }
+    }
+}
+
+// Pitch and formant shifting needs the whole signal, so it is done up front and played out block by block
+struct Shift {
+    sample_rate: u32,
+    pitch_ratio: f64,
+    formant_ratio: f64,
+    shifted: Vec<f32>,
+    position: usize,
+}
+
+impl Shift {
+    fn new(sample_rate: u32, pitch_ratio: f64, formant_ratio: f64) -> Shift {
+        Shift { sample_rate, pitch_ratio, formant_ratio, shifted: Vec::new(), position: 0 }
+    }
+}
+
+impl Effect for Shift {
+    fn prepare(&mut self, samples: &[f32]) {
+        self.shifted = shift::shift(samples, self.sample_rate, self.pitch_ratio, self.formant_ratio);
+        self.position = 0;
+    }
+
+    fn process(&mut self, block: &mut [f32]) {
+        for sample in block {
+            *sample = self.shifted.get(self.position).copied().unwrap_or(0.0);
+            self.position += 1;
+        }
+    }
+}
+
+struct Normalize {
+    target: f64,
+    sample_rate: u32,
+    gain: f32,
+}
+
+impl Effect for Normalize {
+    fn prepare(&mut self, samples: &[f32]) {
+        let gain_db = Loudness::measure(samples, self.sample_rate).gain_to(self.target).map_or(0.0, |(gain_db, _)| gain_db);
+        self.gain = 10f32.powf(gain_db as f32 / 20.0);
+    }
+
+    fn process(&mut self, block: &mut [f32]) {
+        for sample in block {
+            *sample *= self.gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_round_trip_through_their_text() {
+        let chain: Chain = "hpf:80hz, gain:3dB ,eq:3000:-4db:2,comp:-20db,pitch:-2st:keep,pitch:1.5,formants:1.1,norm:-23lufs,lpf:8000"
+            .parse().unwrap();
+        assert_eq!(chain.0, vec![
+            EffectSpec::HighPass { freq: 80.0 },
+            EffectSpec::Gain { db: 3.0 },
+            EffectSpec::Eq { freq: 3000.0, gain_db: -4.0, q: 2.0 },
+            EffectSpec::Compressor { threshold: -20.0, ratio: DEFAULT_RATIO },
+            EffectSpec::Pitch { semitones: Semitones(-2.0), keep_formants: true },
+            EffectSpec::Pitch { semitones: Semitones(1.5), keep_formants: false },
+            EffectSpec::Formants { ratio: 1.1 },
+            EffectSpec::Normalize { target: -23.0 },
+            EffectSpec::LowPass { freq: 8000.0 },
+        ]);
+        let text = chain.to_string();
+        assert_eq!(text, "hpf:80,gain:+3db,eq:3000:-4db:2,comp:-20db:4,pitch:-2st:keep,pitch:+1.5st,formants:1.1,norm:-23,lpf:8000");
+        assert_eq!(text.parse::<Chain>().unwrap(), chain);
+        assert!("".parse::<Chain>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn bad_effects_are_rejected() {
+        for bad in [
+            "gain", "gain:loud", "gain:3:4", "hpf", "eq:3000", "eq:3000:-4db:0", "comp:-20db:0.5",
+            "pitch:+30st", "pitch:+3st:drop", "formants:2", "norm", "reverb:1", "hpf:80,lpf",
+        ] {
+            assert!(bad.parse::<Chain>().is_err(), "{}", bad);
+        }
+        // Frequencies are only checked against the sample rate once the chain is built
+        let chain: Chain = "hpf:9000".parse().unwrap();
+        assert!(chain.apply(&mut [0.0; 16], 16000).is_err());
+        assert!(chain.apply(&mut [0.0; 16], 48000).is_ok());
+    }
+}
@@ -0,0 +1,151 @@
+/// Detection of vocal fry (creak) and pitch breaks in a clip's analysis
+use std::fmt;
+use crate::analysis::{Analysis, HOP, Metric};
+use crate::audio_clips::{AudioClip, Speech};
+use crate::dsp;
+use crate::pitch::PitchTracker;
+
+/// Frames below this fraction of the clip's median pitch count as low
+const CREAK_PITCH_RATIO: f32 = 0.65;
+/// Frames with a first minus second harmonic level below this are pressed like creak, in dB
+const CREAK_MAX_H1_H2: f32 = 0.0;
+/// Frame to frame pitch changes above this fraction count as irregular periods
+const CREAK_IRREGULARITY: f32 = 0.1;
+/// Unvoiced frames need at least this clarity for their pitch candidate to be trusted
+const CREAK_MIN_CLARITY: f32 = 0.5;
+/// Frames more than this far below the median voiced level are too quiet to be creak, in dB
+const CREAK_MAX_LEVEL_DROP: f32 = 15.0;
+/// Gaps between creaky frames up to this long are bridged, in seconds
+const CREAK_MAX_GAP: f64 = 0.03;
+/// Creak shorter than this is ignored, in seconds
+const MIN_CREAK: f64 = 0.05;
+/// Pitch jumps of at least this many semitones between neighbouring frames are breaks
+const BREAK_SEMITONES: f32 = 6.0;
+/// Voiced frames further apart than this are not compared for breaks, in seconds
+const BREAK_MAX_GAP: f64 = 0.06;
+/// Frames the pitch has to stay near its new value for a jump to count, ruling out single-frame tracking errors
+const BREAK_PERSISTENCE: usize = 3;
+
+/// What kind of event was detected
+#[derive(Debug, Clone, Copy)]
+pub enum EventKind {
+    /// Irregular low pitched phonation
+    Creak,
+    /// Abrupt jump between two pitches in Hz
+    PitchBreak { from: f32, to: f32 },
+}
+
+/// An event and where it happens in the edited clip, in seconds
+#[derive(Debug, Clone, Copy)]
+pub struct VoiceEvent {
+    pub kind: EventKind,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl fmt::Display for VoiceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            EventKind::Creak => write!(f, "{:8.2}s - {:8.2}s creak ({:.2}s)", self.start, self.end, self.end - self.start),
+            EventKind::PitchBreak { from, to } => write!(
+                f,
+                "{:8.2}s - {:8.2}s pitch break {:.0} Hz -> {:.0} Hz ({:+.1} semitones)",
+                self.start, self.end, from, to, semitones(from, to),
+            ),
+        }
+    }
+}
+
+/// Creaky stretches and pitch breaks of an analysed clip, in time order
+pub fn detect(audio_clip: &AudioClip, speech: &Speech, analysis: &Analysis) -> Vec<VoiceEvent> {
+    let Some(median) = analysis.median(Metric::Pitch) else { return Vec::new() };
+    let mut events = creak(audio_clip, speech, analysis, median);
+    events.extend(pitch_breaks(analysis));
+    events.sort_by(|a, b| a.start.total_cmp(&b.start));
+    events
+}
+
+/// Pitch breaks of an analysed clip, in time order
+pub fn pitch_breaks(analysis: &Analysis) -> Vec<VoiceEvent> {
+    let frames = &analysis.frames;
+    let mut events = Vec::new();
+
+    // Pitch breaks: a large jump between neighbouring frames after which the pitch stays near its new value.
+    // The tracker often drops a few frames while its window straddles the jump
+    let close = |i: usize| i > 0 && frames[i].time - frames[i - 1].time <= BREAK_MAX_GAP;
+    for i in 1..frames.len() {
+        if !close(i) || semitones(frames[i - 1].f0, frames[i].f0).abs() < BREAK_SEMITONES {
+            continue;
+        }
+        let persists = (i..i + BREAK_PERSISTENCE).all(|j| {
+            j < frames.len() && (j == i || close(j)) && semitones(frames[i].f0, frames[j].f0).abs() < BREAK_SEMITONES / 2.0
+        });
+        if persists {
+            events.push(VoiceEvent {
+                kind: EventKind::PitchBreak { from: frames[i - 1].f0, to: frames[i].f0 },
+                start: frames[i - 1].time,
+                end: frames[i].time,
+            });
+        }
+    }
+
+    events
+}
+
+// Stretches of low pitched speech that is either pressed or irregular. Creak is often too aperiodic for the
+// pitch tracker to call voiced, so loud frames with a clear enough low pitch candidate count as irregular too
+fn creak(audio_clip: &AudioClip, speech: &Speech, analysis: &Analysis, median: f32) -> Vec<VoiceEvent> {
+    let sample_rate = audio_clip.sample_rate as f64;
+    let tracker = PitchTracker::new(audio_clip.sample_rate);
+    let hop = (HOP * sample_rate) as usize;
+    let loud = analysis.median(Metric::Level).unwrap_or(f32::MIN) - CREAK_MAX_LEVEL_DROP;
+
+    let mut events = Vec::new();
+    for segment in speech.segments.iter().cloned() {
+        // (time, pitch candidate if it is low, whether the frame is pressed or too aperiodic to count as voiced)
+        let candidates: Vec<(f64, Option<f32>, bool)> = dsp::frames(&speech.samples[segment.clone()], tracker.frame_len(), hop)
+            .map(|(start, frame)| {
+                let time = (segment.start + start + tracker.frame_len() / 2) as f64 / sample_rate;
+                let voiced = analysis.frames_near(time, HOP / 2.0).iter().find(|frame| (frame.time - time).abs() < HOP / 2.0);
+                let Some((f0, clarity)) = tracker.candidate(frame) else { return (time, None, false) };
+                let low = f0 < CREAK_PITCH_RATIO * median && dsp::rms_db(frame) >= loud;
+                let creaky = match voiced {
+                    Some(voiced) => voiced.h1_h2.is_some_and(|h1_h2| h1_h2 < CREAK_MAX_H1_H2),
+                    None => clarity >= CREAK_MIN_CLARITY,
+                };
+                (time, low.then_some(f0), creaky)
+            })
+            .collect();
+        let irregular = |i: usize| {
+            let Some(f0) = candidates[i].1 else { return false };
+            [i.checked_sub(1), Some(i + 1)].into_iter().flatten()
+                .filter_map(|j| candidates.get(j).and_then(|candidate| candidate.1))
+                .any(|other| (other - f0).abs() / f0 > CREAK_IRREGULARITY)
+        };
+
+        // Runs of creaky frames, bridging short gaps where single frames look regular
+        let times: Vec<f64> = (0..candidates.len())
+            .filter(|&i| candidates[i].1.is_some() && (candidates[i].2 || irregular(i)))
+            .map(|i| candidates[i].0)
+            .collect();
+        let mut runs: Vec<(f64, f64)> = Vec::new();
+        for time in times {
+            match runs.last_mut() {
+                Some((_, end)) if time - *end <= CREAK_MAX_GAP => *end = time,
+                _ => runs.push((time, time)),
+            }
+        }
+        for (start, end) in runs {
+            let (start, end) = (start - HOP / 2.0, end + HOP / 2.0);
+            if end - start >= MIN_CREAK {
+                events.push(VoiceEvent { kind: EventKind::Creak, start, end });
+            }
+        }
+    }
+    events
+}
+
+// Interval from one pitch to another in semitones
+fn semitones(from: f32, to: f32) -> f32 {
+    12.0 * (to / from).log2()
+}
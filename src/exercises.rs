@@ -0,0 +1,70 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Guided practice exercises made of prompted, timed recording steps
+use clap::ValueEnum;
+use color_eyre::eyre::{Report, Result, eyre};
+
+/// What an exercise trains, which decides how its recordings are analysed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExerciseKind {
+    /// Sustained vowels, measured for pitch stability and voice quality
+    Vowel,
+    /// Pitch glides and sirens, measured for the pitch range covered
+    Glide,
+    /// Reading passages, measured for prosody and resonance
+    Reading,
+}
+
+impl ExerciseKind {
+    pub const ALL: [ExerciseKind; 3] = [ExerciseKind::Vowel, ExerciseKind::Glide, ExerciseKind::Reading];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExerciseKind::Vowel => "vowel",
+            ExerciseKind::Glide => "glide",
+            ExerciseKind::Reading => "reading",
+        }
+    }
+}
+
+impl fmt::Display for ExerciseKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ExerciseKind {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<ExerciseKind> {
+        ExerciseKind::ALL.into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or(eyre!("Unknown exercise kind '{}'", s))
+    }
+}
+
+/// One prompted recording of an exercise
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub prompt: String,
+    /// Longest recording time in seconds
+    pub duration: f64,
+}
+
+/// A named sequence of recording steps
+#[derive(Debug, Clone)]
+pub struct Exercise {
+    pub id: Option<usize>,
+    pub name: String,
+    pub kind: ExerciseKind,
+    pub description: String,
+    pub steps: Vec<Step>,
+}
+
+impl Exercise {
+    /// Total recording time of all steps in seconds
+    pub fn duration(&self) -> f64 {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+}
@@ -0,0 +1,71 @@
+/// Formant estimation from linear prediction
+use crate::dsp;
+
+/// Sample rate the signal is decimated to before analysis, in Hz; covers formants up to about 5.5 kHz
+const ANALYSIS_RATE: u32 = 11000;
+/// Linear prediction order: two poles per formant plus two for the spectral tilt
+const LPC_ORDER: usize = 12;
+/// Analysis window length in seconds
+const WINDOW: f64 = 0.025;
+/// Poles wider than this are not formants, in Hz
+const MAX_BANDWIDTH: f64 = 800.0;
+/// Poles below this are not formants, in Hz
+const MIN_FREQUENCY: f64 = 90.0;
+/// Poles broader than this fraction of their frequency model the glottal source rather than a formant
+const MAX_RELATIVE_BANDWIDTH: f64 = 0.5;
+
+/// Estimates the first formants of short frames of a clip
+pub struct FormantTracker {
+    sample_rate: f64,
+    samples: Vec<f32>,
+}
+
+impl FormantTracker {
+    /// Prepare `samples` (recorded at `sample_rate`) for formant analysis
+    pub fn new(samples: &[f32], sample_rate: u32) -> FormantTracker {
+        let factor = ((sample_rate as f64 / ANALYSIS_RATE as f64).round() as usize).max(1);
+        let decimated = dsp::decimate(samples, factor);
+        // Pre-emphasis so the higher formants are not swamped by the glottal tilt
+        let mut previous = 0.0;
+        let samples = decimated.iter()
+            .map(|&sample| {
+                let emphasised = sample - 0.97 * previous;
+                previous = sample;
+                emphasised
+            })
+            .collect();
+        FormantTracker {
+            sample_rate: sample_rate as f64 / factor as f64,
+            samples,
+        }
+    }
+
+    /// The first three formant frequencies in Hz around `time` seconds, if they can be found
+    pub fn estimate(&self, time: f64) -> Option<[f32; 3]> {
+        let len = (WINDOW * self.sample_rate) as usize;
+        let centre = (time * self.sample_rate) as usize;
+        let start = centre.checked_sub(len / 2)?;
+        let frame = self.samples.get(start..start + len)?;
+        let window = dsp::hann(len);
+        let windowed: Vec<f32> = frame.iter().zip(&window).map(|(s, w)| s * w).collect();
+
+        let coefficients = dsp::lpc(&windowed, LPC_ORDER)?;
+        let mut formants: Vec<f64> = dsp::polynomial_roots(&coefficients)
+            .into_iter()
+            .filter(|root| root.im > 0.0)
+            .filter_map(|root| {
+                let frequency = root.arg() * self.sample_rate / (2.0 * std::f64::consts::PI);
+                let bandwidth = -root.norm().ln() * self.sample_rate / std::f64::consts::PI;
+                let is_formant = frequency > MIN_FREQUENCY
+                    && bandwidth < MAX_BANDWIDTH
+                    && bandwidth < MAX_RELATIVE_BANDWIDTH * frequency;
+                is_formant.then_some(frequency)
+            })
+            .collect();
+        formants.sort_by(f64::total_cmp);
+        match formants[..] {
+            [f1, f2, f3, ..] => Some([f1 as f32, f2 as f32, f3 as f32]),
+            _ => None,
+        }
+    }
+}
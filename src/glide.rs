@@ -0,0 +1,140 @@
+/// Scoring of pitch glide and siren recordings
+use color_eyre::eyre::{Result, eyre};
+use crate::analysis::{Analysis, LazyAnalysis, Metric};
+use crate::db::Db;
+use crate::events;
+
+/// Glides with fewer voiced frames than this are not scored
+const MIN_FRAMES: usize = 10;
+/// Silences between voiced frames longer than this count as dropouts, in seconds
+const MIN_DROPOUT: f64 = 0.1;
+
+/// How a glide moved through the pitch range. Frequencies are in Hz
+#[derive(Debug, Clone)]
+pub struct GlideScore {
+    pub start: f32,
+    pub end: f32,
+    pub min: f32,
+    pub max: f32,
+    /// Interval from the lowest to the highest pitch in semitones
+    pub coverage: f32,
+    /// Root mean square distance of the pitch from the closest one or two part monotone fit, in semitones.
+    /// Lower is smoother
+    pub smoothness: f32,
+    /// Number of abrupt pitch breaks
+    pub breaks: usize,
+    /// Number of times the voice dropped out in the middle of the glide
+    pub dropouts: usize,
+}
+
+impl GlideScore {
+    /// Score a glide, or None if it has too little voiced audio
+    pub fn of(analysis: &Analysis) -> Option<GlideScore> {
+        let pitches = analysis.values(Metric::Pitch);
+        if pitches.len() < MIN_FRAMES {
+            return None;
+        }
+        let min = pitches.iter().copied().fold(f32::MAX, f32::min);
+        let max = pitches.iter().copied().fold(f32::MIN, f32::max);
+        let semitones: Vec<f32> = pitches.iter().map(|f0| 12.0 * (f0 / min).log2()).collect();
+
+        let breaks = events::pitch_breaks(analysis).len();
+        let dropouts = analysis.frames.windows(2).filter(|pair| pair[1].time - pair[0].time > MIN_DROPOUT).count();
+
+        Some(GlideScore {
+            start: pitches[0],
+            end: pitches[pitches.len() - 1],
+            min,
+            max,
+            coverage: 12.0 * (max / min).log2(),
+            smoothness: smoothness(&semitones),
+            breaks,
+            dropouts,
+        })
+    }
+}
+
+/// Glide score of a clip, stored in the database the first time it is computed
+pub fn score(db: &Db, analysis: &mut LazyAnalysis) -> Result<Option<GlideScore>> {
+    let audio_clip = analysis.audio_clip;
+    let clip_id = audio_clip.id.ok_or(eyre!("Clip '{}' has not been saved yet", audio_clip.name))?;
+    if let Some(score) = db.glide_score(clip_id)? {
+        return Ok(Some(score));
+    }
+    let score = GlideScore::of(analysis.get()?);
+    if let Some(score) = &score {
+        db.save_glide_score(clip_id, score)?;
+    }
+    Ok(score)
+}
+
+// Residual of the best fit that rises or falls monotonically, or turns once at the highest or lowest point
+// (a siren goes up and back down)
+fn smoothness(contour: &[f32]) -> f32 {
+    let argmax = (0..contour.len()).max_by(|&a, &b| contour[a].total_cmp(&contour[b])).unwrap_or(0);
+    let argmin = (0..contour.len()).min_by(|&a, &b| contour[a].total_cmp(&contour[b])).unwrap_or(0);
+    let turning = |turn: usize, rising_first: bool| {
+        let (first, second) = contour.split_at(turn + 1);
+        squared_residual(first, rising_first) + squared_residual(second, !rising_first)
+    };
+    let best = [
+        squared_residual(contour, true),
+        squared_residual(contour, false),
+        turning(argmax, true),
+        turning(argmin, false),
+    ].into_iter().fold(f32::MAX, f32::min);
+    (best / contour.len() as f32).sqrt()
+}
+
+// Sum of squared differences from the least squares monotone fit (pool adjacent violators)
+fn squared_residual(values: &[f32], increasing: bool) -> f32 {
+    let sign = if increasing { 1.0 } else { -1.0 };
+    // Blocks of pooled values as (sum, count), kept non-decreasing in mean
+    let mut blocks: Vec<(f32, usize)> = Vec::new();
+    for &value in values {
+        blocks.push((sign * value, 1));
+        while blocks.len() > 1 {
+            let (sum, count) = blocks[blocks.len() - 1];
+            let (previous_sum, previous_count) = blocks[blocks.len() - 2];
+            if previous_sum / previous_count as f32 <= sum / count as f32 {
+                break;
+            }
+            blocks.pop();
+            *blocks.last_mut().unwrap() = (previous_sum + sum, previous_count + count);
+        }
+    }
+    let fitted = blocks.iter().flat_map(|&(sum, count)| std::iter::repeat_n(sign * sum / count as f32, count));
+    values.iter().zip(fitted).map(|(value, fit)| (value - fit).powi(2)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn monotone_fit_pools_adjacent_violators() {
+        assert_eq!(squared_residual(&[1.0, 2.0, 2.0, 5.0], true), 0.0);
+        // 3 and 2 are pooled to 2.5
+        assert!(close(squared_residual(&[1.0, 3.0, 2.0, 4.0], true), 0.5));
+        // A pooled block that violates the block before it is pooled again: 4, 2 and 0 all become 2
+        assert!(close(squared_residual(&[0.0, 4.0, 2.0, 0.0, 5.0], true), 8.0));
+        assert!(close(squared_residual(&[3.0, 2.0, 1.0], true), 2.0));
+        assert_eq!(squared_residual(&[3.0, 2.0, 1.0], false), 0.0);
+    }
+
+    #[test]
+    fn sirens_turn_once_without_penalty() {
+        assert_eq!(smoothness(&[0.0, 1.0, 2.0, 3.0, 2.0, 1.0, 0.0]), 0.0);
+        assert_eq!(smoothness(&[3.0, 2.0, 1.0, 0.0, 1.0, 2.0, 3.0]), 0.0);
+        assert_eq!(smoothness(&[5.0, 4.0, 3.0, 2.0]), 0.0);
+        // A wobble on the way up costs the same whether or not the glide turns afterwards
+        let rising = [0.0, 1.0, 3.0, 2.0, 4.0, 5.0];
+        let siren = [0.0, 1.0, 3.0, 2.0, 4.0, 5.0, 4.0, 3.0];
+        assert!(close(smoothness(&rising), (0.5f32 / 6.0).sqrt()));
+        assert!(close(smoothness(&siren), (0.5f32 / 8.0).sqrt()));
+    }
+}
@@ -0,0 +1,94 @@
+/// Target ranges for voice metrics and how clips score against them
+use color_eyre::eyre::{Result, eyre};
+use chrono::{DateTime, Utc};
+use crate::analysis::{Analysis, LazyAnalysis, Metric};
+use crate::audio_clips::AudioClip;
+use crate::db::Db;
+
+/// How many earlier sessions a clip is compared with
+pub const PREVIOUS_SESSIONS: usize = 5;
+
+/// A named target range for one metric
+#[derive(Debug, Clone)]
+pub struct Goal {
+    pub id: Option<usize>,
+    pub name: String,
+    pub metric: Metric,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Goal {
+    pub fn describe(&self) -> String {
+        format!("{} ({} {}-{} {})", self.name, self.metric, self.min, self.max, self.metric.unit())
+    }
+}
+
+/// Share of the clip's voiced time inside the goal's range, cached in the database
+pub fn score(db: &Db, analysis: &mut LazyAnalysis, goal: &Goal) -> Result<Option<f32>> {
+    let clip_id = analysis.audio_clip.id.ok_or(eyre!("Clip '{}' has not been saved yet", analysis.audio_clip.name))?;
+    let goal_id = goal.id.ok_or(eyre!("Goal '{}' has not been saved yet", goal.name))?;
+    if let Some(score) = db.cached_goal_score(clip_id, goal_id)? {
+        return Ok(score.map(|score| score as f32));
+    }
+    let score = analysis.get()?.in_range(goal.metric, goal.min, goal.max);
+    db.cache_goal_score(clip_id, goal_id, score.map(f64::from))?;
+    Ok(score)
+}
+
+/// Median of a metric over the clip's voiced time, cached in the database
+pub fn median(db: &Db, analysis: &mut LazyAnalysis, metric: Metric) -> Result<Option<f32>> {
+    let clip_id = analysis.audio_clip.id.ok_or(eyre!("Clip '{}' has not been saved yet", analysis.audio_clip.name))?;
+    if let Some(value) = db.cached_metric(clip_id, metric.name())? {
+        return Ok(value.map(|value| value as f32));
+    }
+    let value = analysis.get()?.median(metric);
+    db.cache_metric(clip_id, metric.name(), value.map(f64::from))?;
+    Ok(value)
+}
+
+/// Mean score of the sessions recorded before `before`, with the number of sessions it covers
+pub fn previous_average(db: &Db, goal: &Goal, before: DateTime<Utc>) -> Result<Option<(f32, usize)>> {
+    let mut scores = Vec::new();
+    for audio_clip in db.clips_before(before, PREVIOUS_SESSIONS)? {
+        if let Some(score) = score(db, &mut LazyAnalysis::new(&audio_clip), goal)? {
+            scores.push(score);
+        }
+    }
+    if scores.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((scores.iter().sum::<f32>() / scores.len() as f32, scores.len())))
+}
+
+/// Print a score against a goal alongside the average of the previous sessions
+pub fn print_score(db: &Db, goal: &Goal, score: Option<f32>, before: DateTime<Utc>) -> Result<()> {
+    let Some(score) = score else {
+        println!("{}: not enough voiced audio to score", goal.describe());
+        return Ok(());
+    };
+    let comparison = match previous_average(db, goal, before)? {
+        Some((previous, sessions)) => format!(
+            " (previous {} session(s): {:.1}%, {:+.1} points)",
+            sessions,
+            previous * 100.0,
+            (score - previous) * 100.0,
+        ),
+        None => " (no previous sessions)".to_string(),
+    };
+    println!("{}: {:.1}% of voiced time in range{}", goal.describe(), score * 100.0, comparison);
+    Ok(())
+}
+
+/// Score an analysed clip against every goal and print the results
+pub fn report(db: &Db, audio_clip: &AudioClip, analysis: &Analysis) -> Result<()> {
+    let clip_id = audio_clip.id.ok_or(eyre!("Clip '{}' has not been saved yet", audio_clip.name))?;
+    for goal in db.goals()? {
+        let score = analysis.in_range(goal.metric, goal.min, goal.max);
+        if let Some(goal_id) = goal.id {
+            db.cache_goal_score(clip_id, goal_id, score.map(f64::from))?;
+        }
+        print_score(db, &goal, score, audio_clip.created_at)?;
+    }
+    Ok(())
+}
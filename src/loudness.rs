@@ -0,0 +1,176 @@
+/// Loudness measurement following EBU R128 / ITU-R BS.1770
+use crate::dsp::Biquad;
+
+/// Absolute gate below which blocks are ignored for integrated loudness, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Relative gate below the ungated loudness, in LU
+const RELATIVE_GATE: f64 = -10.0;
+/// Oversampling factor used to estimate the true peak
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+/// Taps per phase of the true peak interpolation filter
+const TRUE_PEAK_TAPS: usize = 12;
+/// K-weighting head shelf: frequency in Hz, Q and gain in dB
+const SHELF: (f64, f64, f64) = (1681.974, 0.7072, 3.9998);
+/// Exponent giving the shelf's gain at its centre frequency from its high frequency gain
+const SHELF_BAND_EXPONENT: f64 = 0.4996667741545416;
+/// K-weighting high pass: frequency in Hz and Q
+const HIGH_PASS: (f64, f64) = (38.135, 0.5003);
+/// Highest true peak normalisation may raise a clip to, in dBTP
+pub const MAX_TRUE_PEAK: f64 = -1.0;
+
+/// Loudness statistics of a clip
+#[derive(Debug, Clone)]
+pub struct Loudness {
+    /// Gated loudness over the whole clip in LUFS (negative infinity for silence)
+    pub integrated: f64,
+    /// Loudness of 400 ms blocks every 100 ms in LUFS
+    pub momentary: Vec<f64>,
+    /// Loudness of 3 s blocks every 100 ms in LUFS
+    pub short_term: Vec<f64>,
+    /// Highest inter-sample peak in dBTP
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// Measure the loudness of mono samples
+    pub fn measure(samples: &[f32], sample_rate: u32) -> Loudness {
+        let weighted = k_weight(samples, sample_rate);
+        let hop = sample_rate as usize / 10;
+        let momentary_powers = block_powers(&weighted, 4 * hop, hop);
+        let short_term_powers = block_powers(&weighted, 30 * hop, hop);
+
+        Loudness {
+            integrated: integrated(&momentary_powers),
+            momentary: momentary_powers.iter().map(|&power| power_to_lufs(power)).collect(),
+            short_term: short_term_powers.iter().map(|&power| power_to_lufs(power)).collect(),
+            true_peak: true_peak(samples),
+        }
+    }
+
+    /// Loudest momentary block, or None if the clip is silent or shorter than a block
+    pub fn max_momentary(&self) -> Option<f64> {
+        self.momentary.iter().copied().filter(|lufs| lufs.is_finite()).reduce(f64::max)
+    }
+
+    /// Loudest short-term block, or None if the clip is silent or shorter than a block
+    pub fn max_short_term(&self) -> Option<f64> {
+        self.short_term.iter().copied().filter(|lufs| lufs.is_finite()).reduce(f64::max)
+    }
+
+    /// Gain in dB that brings the integrated loudness to `target` LUFS, or None for silent clips. The gain is
+    /// limited so the true peak does not go above `MAX_TRUE_PEAK`; the flag is set when that limit was hit
+    pub fn gain_to(&self, target: f64) -> Option<(f64, bool)> {
+        if !self.integrated.is_finite() {
+            return None;
+        }
+        let gain = target - self.integrated;
+        let headroom = MAX_TRUE_PEAK - self.true_peak;
+        Some(if gain > headroom { (headroom, true) } else { (gain, false) })
+    }
+}
+
+// Apply the BS.1770 K-weighting pre-filter (head shelf followed by the RLB high pass). The filters are designed with
+// a prewarped bilinear transform, which reproduces the coefficients published for 48 kHz at any sample rate
+fn k_weight(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    let sample_rate = sample_rate as f64;
+    let (freq, q, gain_db) = SHELF;
+    let k = (std::f64::consts::PI * freq / sample_rate).tan();
+    let high_gain = 10f64.powf(gain_db / 20.0);
+    let band_gain = high_gain.powf(SHELF_BAND_EXPONENT);
+    let mut shelf = Biquad::new(
+        [high_gain + band_gain * k / q + k * k, 2.0 * (k * k - high_gain), high_gain - band_gain * k / q + k * k],
+        [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+    );
+    let (freq, q) = HIGH_PASS;
+    let k = (std::f64::consts::PI * freq / sample_rate).tan();
+    let mut high_pass = Biquad::new([1.0, -2.0, 1.0], [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k]);
+    samples.iter().map(|&sample| high_pass.process(shelf.process(sample))).collect()
+}
+
+// Mean square of each block of `len` samples every `hop` samples
+fn block_powers(samples: &[f32], len: usize, hop: usize) -> Vec<f64> {
+    crate::dsp::frames(samples, len, hop)
+        .map(|(_, block)| block.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / len as f64)
+        .collect()
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+// Two stage gated loudness over the momentary blocks
+fn integrated(powers: &[f64]) -> f64 {
+    let mean_lufs = |powers: &mut dyn Iterator<Item = f64>| {
+        let (sum, count) = powers.fold((0.0, 0usize), |(sum, count), power| (sum + power, count + 1));
+        if count == 0 { f64::NEG_INFINITY } else { power_to_lufs(sum / count as f64) }
+    };
+    let above_absolute = |power: &&f64| power_to_lufs(**power) > ABSOLUTE_GATE;
+    let ungated = mean_lufs(&mut powers.iter().filter(above_absolute).copied());
+    if !ungated.is_finite() {
+        return f64::NEG_INFINITY;
+    }
+    let relative_gate = ungated + RELATIVE_GATE;
+    mean_lufs(&mut powers.iter().filter(above_absolute).filter(|&&power| power_to_lufs(power) > relative_gate).copied())
+}
+
+// Peak of the signal oversampled with a windowed sinc interpolator, in dBTP
+fn true_peak(samples: &[f32]) -> f64 {
+    let half = TRUE_PEAK_TAPS as isize / 2;
+    let taps: Vec<Vec<f64>> = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let offset = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+            (-half + 1..=half)
+                .map(|k| {
+                    let x = k as f64 - offset;
+                    let sinc = (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x);
+                    let window = 0.5 + 0.5 * (std::f64::consts::PI * x / half as f64).cos();
+                    sinc * window
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut peak = samples.iter().fold(0f64, |peak, &s| peak.max((s as f64).abs()));
+    for i in 0..samples.len() {
+        for phase_taps in &taps {
+            let value: f64 = phase_taps.iter().enumerate()
+                .filter_map(|(j, tap)| {
+                    let index = i as isize + j as isize - half + 1;
+                    samples.get(usize::try_from(index).ok()?).map(|&s| s as f64 * tap)
+                })
+                .sum();
+            peak = peak.max(value.abs());
+        }
+    }
+    20.0 * peak.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // BS.1770 calibration: a full scale 997 Hz sine measures -3.01 LUFS
+    #[test]
+    fn full_scale_sine_is_minus_three_lufs() {
+        let sample_rate = 48000;
+        let samples: Vec<f32> = (0..5 * sample_rate)
+            .map(|i| (2.0 * std::f64::consts::PI * 997.0 * i as f64 / sample_rate as f64).sin() as f32)
+            .collect();
+        let loudness = Loudness::measure(&samples, sample_rate);
+        assert!((loudness.integrated + 3.01).abs() < 0.1, "{}", loudness.integrated);
+    }
+
+    #[test]
+    fn normalisation_gain_keeps_the_true_peak_down() {
+        let sample_rate = 48000;
+        let samples: Vec<f32> = (0..5 * sample_rate)
+            .map(|i| 0.5 * (2.0 * std::f64::consts::PI * 997.0 * i as f64 / sample_rate as f64).sin() as f32)
+            .collect();
+        let loudness = Loudness::measure(&samples, sample_rate);
+        let (gain, limited) = loudness.gain_to(-23.0).unwrap();
+        assert!(!limited && (loudness.integrated + gain + 23.0).abs() < 1e-9);
+        let (gain, limited) = loudness.gain_to(0.0).unwrap();
+        assert!(limited && (loudness.true_peak + gain - MAX_TRUE_PEAK).abs() < 1e-9);
+        assert_eq!(Loudness::measure(&samples[..sample_rate as usize], sample_rate).max_short_term(), None);
+    }
+}
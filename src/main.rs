@@ -1,10 +1,46 @@
+mod analysis;
 mod audio_clips;
+mod breathiness;
+mod compare;
 mod db;
+mod denoise;
+mod dsp;
+mod edits;
+mod effects;
+mod events;
+mod exercises;
+mod formants;
+mod glide;
+mod goals;
+mod loudness;
+mod monitor;
+mod pitch;
+mod prosody;
+mod quality;
+mod range;
+mod reference;
+mod report;
+mod resonance;
+mod shift;
+mod stretch;
+mod stats;
+#[cfg(test)]
+mod test_signals;
+mod tracks;
+mod vad;
 
-use chrono::Local;
-use clap::{Parser, Subcommand};
-use color_eyre::eyre::Result;
-use audio_clips::AudioClip;
+use chrono::{Days, Duration, Local, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{Result, eyre};
+use audio_clips::{AudioClip, Speech};
+use analysis::{Analysis, LazyAnalysis, Metric};
+use edits::Edit;
+use effects::Chain;
+use exercises::{Exercise, ExerciseKind, Step};
+use goals::Goal;
+use shift::Semitones;
+use std::path::PathBuf;
+use std::io::Write;
 /// A fictional versioning CLI
 #[derive(Debug, Parser)] // requires `derive` feature
 #[command(name = "oxygen")]
@@ -20,6 +56,33 @@ enum Commands {
     Record {
         /// The name of the clip to record. If not provided, the current date and time will be used
         name: Option<String>,
+        /// What to do when a clip with the same name already exists
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Fail)]
+        on_conflict: ConflictPolicy,
+        /// Overwrite without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Trim leading and trailing silence after recording (the original audio is kept)
+        #[arg(long)]
+        auto_trim: bool,
+        /// Reduce background noise learned from the silence in the recording (the original audio is kept)
+        #[arg(long)]
+        denoise: bool,
+    },
+    /// Show a live pitch trace from the default audio device until ctrl + c is pressed
+    Monitor {
+        /// Lower edge of the target pitch band in Hz
+        #[arg(long, requires = "target_max")]
+        target_min: Option<f32>,
+        /// Upper edge of the target pitch band in Hz
+        #[arg(long, requires = "target_min")]
+        target_max: Option<f32>,
+        /// Use the range of this pitch goal as the target band
+        #[arg(long, conflicts_with_all = ["target_min", "target_max"])]
+        goal: Option<String>,
+        /// Also save the session as a clip with this name
+        #[arg(long)]
+        record: Option<String>,
     },
     /// List all the clips in our database
     List {
@@ -29,39 +92,1254 @@ enum Commands {
     Play {
         /// The name of the clip to play
         name: String,
+        /// Play at this integrated loudness in LUFS (e.g. -23) without changing the stored clip
+        #[arg(long, allow_hyphen_values = true)]
+        normalize: Option<f64>,
+        /// Start playing this many seconds into the clip
+        #[arg(long)]
+        start: Option<f64>,
+        /// Play at this speed (0.5 to 2.0) without changing the pitch. The speed can also be changed while playing
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        #[command(flatten)]
+        processing: Processing,
+    },
+    /// Save a processed copy of the clip with given name as a new clip
+    #[command(arg_required_else_help = true)]
+    Render {
+        /// The name of the clip to process
+        name: String,
+        /// The name of the new clip
+        new_name: String,
+        #[command(flatten)]
+        processing: Processing,
     },
-    /// Delete the clip with given name
+    /// Move the clip with given name to the trash
     #[command(arg_required_else_help = true)]
     Delete {
         /// The name of the clip to delete
         name: String,
     },
+    /// Rename the clip with given name
+    #[command(arg_required_else_help = true)]
+    Rename {
+        /// The current name of the clip
+        name: String,
+        /// The new name of the clip
+        new_name: String,
+    },
+    /// Copy the clip with given name under a new name
+    #[command(arg_required_else_help = true)]
+    Duplicate {
+        /// The name of the clip to copy
+        name: String,
+        /// The name of the copy
+        new_name: String,
+    },
+    /// Edit the clip with given name without touching its original audio
+    #[command(arg_required_else_help = true)]
+    Edit {
+        /// The name of the clip to edit
+        name: String,
+        #[command(subcommand)]
+        operation: EditOperation,
+    },
+    /// Show where speech was detected in the clip with given name
+    #[command(arg_required_else_help = true)]
+    Segments {
+        /// The name of the clip to analyse
+        name: String,
+    },
+    /// Export the clip with given name to a WAV file
+    #[command(arg_required_else_help = true)]
+    Export {
+        /// The name of the clip to export
+        name: String,
+        /// The file to write. Defaults to `<name>.wav`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Export at this integrated loudness in LUFS (e.g. -16) without changing the stored clip
+        #[arg(long, allow_hyphen_values = true)]
+        normalize: Option<f64>,
+        #[command(flatten)]
+        processing: Processing,
+    },
+    /// Export the per-frame pitch, formant and level tracks of the clip with given name
+    #[command(arg_required_else_help = true)]
+    Tracks {
+        /// The name of the clip to export
+        name: String,
+        /// The file format to write
+        #[arg(short, long, value_enum, default_value_t = tracks::TrackFormat::Csv)]
+        format: tracks::TrackFormat,
+        /// The file to write. Defaults to the clip name with the format's extension
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Give times from the start of the original recording instead of the edited clip
+        #[arg(long)]
+        recorded: bool,
+    },
+    /// Analyse the pitch and formants of the clip with given name and score it against the goals
+    #[command(arg_required_else_help = true)]
+    Analyze {
+        /// The name of the clip to analyse
+        name: String,
+    },
+    /// Align two clips and compare the pitch and formants of their speech segments
+    #[command(arg_required_else_help = true)]
+    Compare {
+        /// The name of the earlier clip
+        a: String,
+        /// The name of the clip to compare it with
+        b: String,
+        /// Play each segment of the first clip followed by the aligned audio of the second
+        #[arg(long)]
+        play: bool,
+        /// Show how far each clip lies from the profile of this reference clip
+        #[arg(long)]
+        reference: Option<String>,
+    },
+    /// List the creaky stretches and pitch breaks of the clip with given name
+    #[command(arg_required_else_help = true)]
+    Events {
+        /// The name of the clip to check
+        name: String,
+    },
+    /// Measure the speaking rate, pauses and intonation variability of the clip with given name
+    #[command(arg_required_else_help = true)]
+    Prosody {
+        /// The name of the clip to measure
+        name: String,
+    },
+    /// Measure jitter, shimmer and harmonics-to-noise ratio of the clip with given name (best on sustained vowels)
+    #[command(arg_required_else_help = true)]
+    Quality {
+        /// The name of the clip to measure
+        name: String,
+    },
+    /// Score the pitch glide or siren in the clip with given name, or show the stored glide scores of all clips
+    Glide {
+        /// The name of the clip to score
+        name: Option<String>,
+    },
+    /// Measure the spectral centroid, vocal tract length and brightness of the clip with given name
+    #[command(arg_required_else_help = true)]
+    Resonance {
+        /// The name of the clip to measure
+        name: String,
+        /// Write the per-frame resonance tracks to this CSV file for plotting
+        #[arg(long)]
+        tracks: Option<PathBuf>,
+    },
+    /// Build a voice range profile (pitch against level) from the given clips, or from all clips except references
+    Range {
+        /// The names of the clips to include
+        names: Vec<String>,
+        /// Where to write the SVG chart
+        #[arg(short, long, default_value = "range.svg")]
+        output: PathBuf,
+    },
+    /// Show how the clips score against the goals over time
+    Progress {
+        /// Only show this goal
+        #[arg(long)]
+        goal: Option<String>,
+        /// Show each clip's distance from the profile of this reference clip
+        #[arg(long)]
+        reference: Option<String>,
+    },
+    /// Import a WAV file as a new clip
+    #[command(arg_required_else_help = true)]
+    Import {
+        /// The WAV file to import
+        path: PathBuf,
+        /// The name of the new clip. Defaults to the file name without its extension
+        #[arg(long)]
+        name: Option<String>,
+        /// Mark the clip as a reference voice rather than a journal entry
+        #[arg(long)]
+        reference: bool,
+    },
+    /// Manage the clips used as reference voices
+    Reference {
+        #[command(subcommand)]
+        command: ReferenceCommands,
+    },
+    /// Run a guided exercise: record each prompted step and analyse it
+    #[command(arg_required_else_help = true)]
+    Practice {
+        /// The name of the exercise
+        exercise: String,
+    },
+    /// Summarise recorded time, sessions per week, daily streaks and time per exercise
+    Stats {
+        /// How many weeks of sessions to show
+        #[arg(long, default_value_t = 8)]
+        weeks: usize,
+    },
+    /// Write a self-contained HTML report of the practice in a period, e.g. for a coach
+    Report {
+        /// First day to include (YYYY-MM-DD). Defaults to six days before `--to`
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Last day to include (YYYY-MM-DD). Defaults to today
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        /// Where to write the report
+        #[arg(short, long, default_value = "report.html")]
+        output: PathBuf,
+        /// A note to include in the report. Repeat for several paragraphs
+        #[arg(long = "note")]
+        notes: Vec<String>,
+        /// Embed the audio of the clip with given name. Repeat for several clips
+        #[arg(long = "clip")]
+        clips: Vec<String>,
+    },
+    /// Check whether today's practice is done, for reminder scripts. Exits with status 1 if it is not
+    Reminder {
+        /// Minutes of recordings needed today for the practice to count as done
+        #[arg(long, default_value_t = 0.0)]
+        min_minutes: f64,
+        /// Only set the exit status
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Manage the practice exercises
+    Exercise {
+        #[command(subcommand)]
+        command: ExerciseCommands,
+    },
+    /// Manage the default effect chain used for playback, export and rendering
+    Fx {
+        #[command(subcommand)]
+        command: FxCommands,
+    },
+    /// Manage target ranges for voice metrics
+    Goal {
+        #[command(subcommand)]
+        command: GoalCommands,
+    },
+    /// Measure the loudness (EBU R128) of the clip with given name
+    #[command(arg_required_else_help = true)]
+    Loudness {
+        /// The name of the clip to measure
+        name: String,
+    },
+    /// Manage deleted clips
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+}
+
+/// Pitch, formant and effect processing shared by the commands that play or write out a clip
+#[derive(Debug, Args)]
+struct Processing {
+    /// Shift the pitch by this interval, e.g. +3st, keeping the duration
+    #[arg(long, allow_hyphen_values = true)]
+    pitch: Option<Semitones>,
+    /// Keep the formants where they are when shifting the pitch, instead of moving them along
+    #[arg(long, requires = "pitch", conflicts_with = "formants")]
+    keep_formants: bool,
+    /// Multiply the formant frequencies by this ratio (0.7 to 1.4), e.g. 1.1 for a shorter sounding vocal tract.
+    /// The pitch only changes with --pitch
+    #[arg(long)]
+    formants: Option<f64>,
+    /// Run the clip through this effect chain, e.g. "hpf:80,gain:+3db", instead of the default set with `fx set`.
+    /// An empty chain turns the default off
+    #[arg(long, allow_hyphen_values = true)]
+    fx: Option<Chain>,
+}
+
+#[derive(Debug, Subcommand)]
+enum EditOperation {
+    /// Cut audio off the start and end of the clip
+    Trim {
+        /// Seconds to cut off the start
+        #[arg(long, default_value_t = 0.0)]
+        start: f64,
+        /// Where the clip should end in seconds. Zero or negative values count back from the end
+        #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+        end: f64,
+    },
+    /// Keep only the audio between two points
+    Crop {
+        /// Start of the audio to keep in seconds
+        #[arg(long)]
+        start: f64,
+        /// End of the audio to keep in seconds
+        #[arg(long)]
+        end: f64,
+    },
+    /// Split the clip in two; the second part is saved as a new clip
+    Split {
+        /// Where to split in seconds
+        #[arg(long)]
+        at: f64,
+        /// The name of the new clip. Defaults to the clip name with a numeric suffix
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Trim the silence before and after the speech in the clip
+    AutoTrim {
+        /// Seconds of silence to keep around the speech
+        #[arg(long, default_value_t = AUTO_TRIM_PADDING)]
+        padding: f64,
+    },
+    /// Turn down steady background noise such as fans or traffic. The noise is learned from the silence around
+    /// the speech, or from a region holding only noise
+    Denoise {
+        /// Most the noise is turned down, in dB
+        #[arg(long, default_value_t = denoise::DEFAULT_REDUCTION_DB)]
+        reduction: f64,
+        /// Start of the noise region in seconds
+        #[arg(long, requires = "noise_end")]
+        noise_start: Option<f64>,
+        /// End of the noise region in seconds
+        #[arg(long, requires = "noise_start")]
+        noise_end: Option<f64>,
+        /// Only clean up the audio the analysers look at; playback and export keep the noise
+        #[arg(long)]
+        analysis_only: bool,
+    },
+    /// Remove the most recent edit
+    Undo {
+    },
+    /// Remove all edits, restoring the original recording
+    Reset {
+    },
+    /// Show the edits applied to the clip
+    Show {
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum GoalCommands {
+    /// Add a goal, e.g. `goal add pitch-range --metric pitch --min 165 --max 220`
+    #[command(arg_required_else_help = true)]
+    Add {
+        /// The name of the goal
+        name: String,
+        /// The metric the goal is about
+        #[arg(long, value_enum)]
+        metric: Metric,
+        /// Lower edge of the target range
+        #[arg(long)]
+        min: f32,
+        /// Upper edge of the target range
+        #[arg(long)]
+        max: f32,
+    },
+    /// List the goals
+    List {
+    },
+    /// Remove the goal with given name
+    #[command(arg_required_else_help = true)]
+    Remove {
+        /// The name of the goal to remove
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ExerciseCommands {
+    /// List the exercises
+    List {
+    },
+    /// Show the steps of the exercise with given name
+    #[command(arg_required_else_help = true)]
+    Show {
+        /// The name of the exercise
+        name: String,
+    },
+    /// Add an exercise, e.g. `exercise add hum --kind vowel --prompt "Hum on a comfortable pitch" --duration 8`
+    #[command(arg_required_else_help = true)]
+    Add {
+        /// The name of the exercise
+        name: String,
+        /// What the exercise trains, which decides how its recordings are analysed
+        #[arg(long, value_enum)]
+        kind: ExerciseKind,
+        /// A short explanation shown before the exercise starts
+        #[arg(long, default_value = "")]
+        description: String,
+        /// The prompt of a step. Repeat for several steps
+        #[arg(long = "prompt", required = true)]
+        prompts: Vec<String>,
+        /// Longest recording time of each step in seconds
+        #[arg(long, default_value_t = 10.0)]
+        duration: f64,
+    },
+    /// Remove the exercise with given name
+    #[command(arg_required_else_help = true)]
+    Remove {
+        /// The name of the exercise to remove
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum FxCommands {
+    /// Set the default effect chain, e.g. `fx set "hpf:80,comp:-20db:4,norm:-23"`
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// Comma separated effects, applied in order
+        #[arg(allow_hyphen_values = true)]
+        chain: Chain,
+    },
+    /// Show the default effect chain
+    Show {
+    },
+    /// Remove the default effect chain
+    Clear {
+    },
+    /// List the available effects
+    List {
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ReferenceCommands {
+    /// Mark the clip with given name as a reference voice
+    #[command(arg_required_else_help = true)]
+    Add {
+        /// The name of the clip
+        name: String,
+    },
+    /// List the reference clips with their profiles
+    List {
+    },
+    /// Turn the reference clip with given name back into a journal entry
+    #[command(arg_required_else_help = true)]
+    Remove {
+        /// The name of the clip
+        name: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TrashCommands {
+    /// List the clips in the trash
+    List {
+    },
+    /// Restore the clip with given name from the trash
+    #[command(arg_required_else_help = true)]
+    Restore {
+        /// The name of the clip to restore
+        name: String,
+    },
+    /// Permanently delete clips in the trash
+    Empty {
+        /// Only delete clips that have been in the trash for more than this many days
+        #[arg(long)]
+        older_than: Option<i64>,
+    },
+}
+
+/// Seconds of silence kept around speech when trimming automatically
+const AUTO_TRIM_PADDING: f64 = 0.15;
+
+/// Seconds of audio suggested before an event when replaying it
+const EVENT_LEAD_IN: f64 = 0.5;
+
+/// Metrics whose clip medians are shown by `progress`
+const PROGRESS_METRICS: [Metric; 4] = [Metric::Pitch, Metric::Cpps, Metric::H1H2, Metric::Brightness];
+
+// Metrics whose medians are shown after each practice step, by exercise kind
+fn practice_metrics(kind: ExerciseKind) -> &'static [Metric] {
+    match kind {
+        ExerciseKind::Vowel => &[Metric::Pitch, Metric::Cpps, Metric::H1H2],
+        ExerciseKind::Glide => &[],
+        ExerciseKind::Reading => &[Metric::Pitch, Metric::Brightness],
+    }
+}
+
+/// How `record` handles a name that is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConflictPolicy {
+    /// Refuse to record
+    Fail,
+    /// Record under the first free name with a numeric suffix (e.g. `name-2`)
+    Suffix,
+    /// Replace the existing clip's audio, keeping its id
+    Overwrite,
+}
+
+// Ask a yes/no question on stdin, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Profile of the reference clip with given name
+fn load_reference(db: &db::Db, name: &str) -> Result<reference::Profile> {
+    let audio_clip = db.load(name)?;
+    if !audio_clip.reference {
+        return Err(eyre!("Clip '{}' is not a reference. Mark it with `oxygen reference add {}`", name, name));
+    }
+    reference::Profile::of(db, &mut LazyAnalysis::new(&audio_clip))
+}
+
+impl Processing {
+    // Pitch and formant ratios for the shift options, or None if nothing is shifted. Formants follow the pitch
+    // unless they are kept in place or given their own ratio
+    fn shift_ratios(&self) -> Result<Option<(f64, f64)>> {
+        let (min, max) = shift::FORMANT_RATIO_RANGE;
+        if let Some(formants) = self.formants.filter(|formants| !(min..=max).contains(formants)) {
+            return Err(eyre!("Formant ratio {} is outside {}-{}", formants, min, max));
+        }
+        let pitch_ratio = self.pitch.map_or(1.0, |pitch| pitch.ratio());
+        Ok(match (self.pitch, self.formants) {
+            (None, None) => None,
+            (_, Some(formants)) => Some((pitch_ratio, formants)),
+            (Some(_), None) => Some((pitch_ratio, if self.keep_formants { 1.0 } else { pitch_ratio })),
+        })
+    }
+
+    // The effect chain given on the command line, or else the default one
+    fn effect_chain(&self, db: &db::Db) -> Result<Chain> {
+        match &self.fx {
+            Some(chain) => Ok(chain.clone()),
+            None => db.setting(effects::FX_SETTING)?.map_or(Ok(Chain::default()), |chain| chain.parse()),
+        }
+    }
+
+    // The clip shifted and run through the effect chain
+    fn apply(&self, db: &db::Db, mut audio_clip: AudioClip) -> Result<Processed> {
+        let shift = self.shift_ratios()?;
+        if let Some((pitch_ratio, formant_ratio)) = shift {
+            audio_clip = audio_clip.shifted(pitch_ratio, formant_ratio)?;
+        }
+        let chain = self.effect_chain(db)?;
+        if !chain.is_empty() {
+            audio_clip = audio_clip.with_effects(&chain)?;
+        }
+        Ok(Processed { audio_clip, shift, chain })
+    }
+}
+
+// A clip after the processing options, with the shift ratios and the effect chain that were applied to it
+struct Processed {
+    audio_clip: AudioClip,
+    shift: Option<(f64, f64)>,
+    chain: Chain,
+}
+
+// Normalise a loaded clip in memory and report the gain
+fn apply_normalization(audio_clip: &mut AudioClip, target: f64) -> Result<()> {
+    match audio_clip.normalize(target)? {
+        Some((gain_db, false)) => println!("Normalised to {:.1} LUFS ({:+.1} dB)", target, gain_db),
+        Some((gain_db, true)) => println!(
+            "Applied {:+.1} dB, short of {:.1} LUFS, to keep the true peak at {:.1} dBTP",
+            gain_db, target, loudness::MAX_TRUE_PEAK,
+        ),
+        None => println!("Clip '{}' is silent, not normalising", audio_clip.name),
+    }
+    Ok(())
+}
+
+// Print jitter, shimmer and HNR of a clip. Returns false if it has too little voiced audio to measure
+fn print_quality(audio_clip: &AudioClip, speech: &Speech) -> bool {
+    let Some(quality) = quality::VoiceQuality::of(audio_clip, speech) else {
+        return false;
+    };
+    println!("Periods measured: {}", quality.periods);
+    println!("Jitter (local): {:.3}%", quality.jitter_local * 100.0);
+    println!("Jitter (RAP): {:.3}%", quality.jitter_rap * 100.0);
+    println!("Shimmer (local): {:.3}%", quality.shimmer_local * 100.0);
+    println!("Shimmer (APQ11): {:.3}%", quality.shimmer_apq11 * 100.0);
+    println!("Harmonics-to-noise ratio: {:.1} dB", quality.hnr);
+    true
+}
+
+// Print speaking rate, pauses and intonation
+fn print_prosody(prosody: &prosody::Prosody) {
+    let rate = |rate: Option<f64>| rate.map_or("-".to_string(), |rate| format!("{:.2} syllables/s", rate));
+    println!("Syllables: {}", prosody.syllables);
+    println!("Articulation rate: {} ({:.2}s of speech)", rate(prosody.articulation_rate()), prosody.speaking_time);
+    println!("Speech rate: {} ({:.2}s including pauses)", rate(prosody.speech_rate()), prosody.total_time);
+    let pause_time: f64 = prosody.pauses.iter().sum();
+    let longest = prosody.pauses.iter().copied().fold(0.0, f64::max);
+    if prosody.pauses.is_empty() {
+        println!("Pauses: 0");
+    } else {
+        println!(
+            "Pauses: {} ({:.2}s in total, {:.2}s on average, longest {:.2}s)",
+            prosody.pauses.len(), pause_time, pause_time / prosody.pauses.len() as f64, longest,
+        );
+    }
+    match prosody.pitch_sd {
+        Some(pitch_sd) => println!("Pitch variability: {:.2} semitones (standard deviation)", pitch_sd),
+        None => println!("Pitch variability: -"),
+    }
+    println!("Pitch rises: {}, falls: {}", prosody.rises, prosody.falls);
+}
+
+// Print how a glide moved through the pitch range
+fn print_glide(score: &glide::GlideScore) {
+    let pitch = |f0: f32| format!("{:.0} Hz ({})", f0, pitch::note_name(f0));
+    println!("Start: {}, end: {}", pitch(score.start), pitch(score.end));
+    println!("Range: {} - {}, {:.1} semitones", pitch(score.min), pitch(score.max), score.coverage);
+    println!("Smoothness: {:.2} semitones from a monotone fit (lower is smoother)", score.smoothness);
+    println!("Pitch breaks: {}, voice dropouts: {}", score.breaks, score.dropouts);
+}
+
+// Print the medians of the given metrics, caching them for the clip
+fn print_medians(db: &db::Db, audio_clip: &AudioClip, analysis: &Analysis, metrics: &[Metric]) -> Result<()> {
+    let clip_id = audio_clip.id.ok_or(eyre!("Clip '{}' has not been saved yet", audio_clip.name))?;
+    for metric in metrics {
+        let median = analysis.median(*metric);
+        db.cache_metric(clip_id, metric.name(), median.map(f64::from))?;
+        match median {
+            Some(median) => println!("Median {}: {:.1} {}", metric, median, metric.unit()),
+            None => println!("Median {}: -", metric),
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
     let cli = Cli::parse();
     let db = db::Db::open("oxygen.db")?;
+    db.purge_expired_trash()?;
     match &cli.command {
-        Commands::Record { name } => {
+        Commands::Record { name, on_conflict, yes, auto_trim, denoise } => {
             let name = name.clone().unwrap_or_else(|| Local::now().format("%Y-%m-%d_%H-%M-%S").to_string());
-            let mut audio_clip = AudioClip::record(name)?;
-            db.save(&mut audio_clip)?;
+            // Resolve conflicts before recording so nothing is lost afterwards
+            let existing = if db.exists(&name)? {
+                match on_conflict {
+                    // A trashed clip keeps its name until it is restored or purged, so it is never recorded over
+                    ConflictPolicy::Fail | ConflictPolicy::Overwrite if db.in_trash(&name)? => {
+                        return Err(eyre!("A clip named '{}' is in the trash (restore it or empty the trash first)", name));
+                    }
+                    ConflictPolicy::Fail => {
+                        return Err(eyre!("A clip named '{}' already exists (use --on-conflict suffix or overwrite)", name));
+                    }
+                    ConflictPolicy::Suffix => None,
+                    ConflictPolicy::Overwrite => {
+                        if !*yes && !confirm(&format!("Overwrite existing clip '{}'?", name))? {
+                            println!("Recording cancelled.");
+                            return Ok(());
+                        }
+                        Some(db.load(&name)?)
+                    }
+                }
+            } else {
+                None
+            };
+            let name = if existing.is_none() && *on_conflict == ConflictPolicy::Suffix {
+                db.unique_name(&name)?
+            } else {
+                name
+            };
+
+            let mut recorded = AudioClip::record(name)?;
+            // Reduce the noise before trimming so the leading and trailing silence can be learned from
+            if *denoise {
+                let edit = Edit::Denoise { reduction: denoise::DEFAULT_REDUCTION_DB, noise: None, analysis_only: false };
+                if let Err(e) = recorded.add_edit(edit) {
+                    println!("{}, keeping the noise.", e);
+                }
+            }
+            if *auto_trim && !recorded.trim_silence(AUTO_TRIM_PADDING)? {
+                println!("No speech detected, keeping the whole recording.");
+            }
+            match existing {
+                Some(mut audio_clip) => {
+                    audio_clip.samples = recorded.samples;
+                    audio_clip.sample_rate = recorded.sample_rate;
+                    audio_clip.created_at = recorded.created_at;
+                    audio_clip.edits = recorded.edits;
+                    audio_clip.playback_position = 0;
+                    db.update(&audio_clip)?;
+                    println!("Overwrote clip '{}'.", audio_clip.name);
+                }
+                None => {
+                    let mut audio_clip = recorded;
+                    db.create(&mut audio_clip)?;
+                    println!("Saved clip '{}'.", audio_clip.name);
+                }
+            }
+        }
+        Commands::Monitor { target_min, target_max, goal, record } => {
+            if let Some(name) = record {
+                db.ensure_name_free(name)?;
+            }
+            let goal = goal.as_deref().map(|goal| db.load_goal(goal)).transpose()?;
+            if let Some(goal) = goal.as_ref().filter(|goal| goal.metric != Metric::Pitch) {
+                return Err(eyre!("Goal '{}' is about {}, the monitor needs a pitch goal", goal.name, goal.metric));
+            }
+            let target = match &goal {
+                Some(goal) => Some((goal.min, goal.max)),
+                None => target_min.zip(*target_max),
+            };
+            let name = record.clone().unwrap_or_default();
+            let session = monitor::run(name, target, record.is_some())?;
+            match session.audio_clip {
+                Some(mut audio_clip) => {
+                    db.create(&mut audio_clip)?;
+                    println!("Saved clip '{}'.", audio_clip.name);
+                    goals::report(&db, &audio_clip, &Analysis::of(&audio_clip)?)?;
+                }
+                None => {
+                    if let Some(goal) = goal {
+                        goals::print_score(&db, &goal, session.in_target, Utc::now())?;
+                    } else if let Some(in_target) = session.in_target {
+                        println!("{:.1}% of voiced time in target", in_target * 100.0);
+                    }
+                }
+            }
         }
         Commands::List {} => {
             let audio_clips = db.list()?;
             for audio_clip in audio_clips {
-                println!("{} {} {} {} ",  audio_clip.name, audio_clip.created_at, audio_clip.sample_rate, audio_clip.playback_position);
+                let edited = if audio_clip.edits.is_empty() { "" } else { " (edited)" };
+                let reference = if audio_clip.reference { " (reference)" } else { "" };
+                let exercise = audio_clip.exercise.as_ref().map_or(String::new(), |exercise| format!(" [{}]", exercise));
+                println!("{} {} {} {}{}{}{}",  audio_clip.name, audio_clip.created_at, audio_clip.sample_rate, audio_clip.playback_position, edited, reference, exercise);
             }
         }
-        Commands::Play { name } => {
-            let audio_clip = db.load(name)?;
-            audio_clip.play()?;
+        Commands::Play { name, normalize, start, speed, processing } => {
+            let (min, max) = stretch::SPEED_RANGE;
+            if !(min..=max).contains(speed) {
+                return Err(eyre!("The speed must be between {} and {}", min, max));
+            }
+            let mut audio_clip = db.load(name)?;
+            if let Some(target) = normalize {
+                apply_normalization(&mut audio_clip, *target)?;
+            }
+            let mut audio_clip = processing.apply(&db, audio_clip)?.audio_clip;
+            if let Some(start) = start {
+                audio_clip.add_edit(Edit::Trim { start: *start, end: 0.0 })?;
+            }
+            audio_clip.play_at(*speed, true)?;
+        }
+        Commands::Render { name, new_name, processing } => {
+            db.ensure_name_free(new_name)?;
+            let Processed { mut audio_clip, shift, chain } = processing.apply(&db, db.load(name)?)?;
+            if shift.is_none() && chain.is_empty() {
+                return Err(eyre!("Nothing to render, choose a processing option such as --pitch, --formants or --fx"));
+            }
+            if let Some((pitch_ratio, formant_ratio)) = shift {
+                println!("Shifted pitch x{:.3} and formants x{:.3}.", pitch_ratio, formant_ratio);
+            }
+            if !chain.is_empty() {
+                println!("Applied effects {}.", chain);
+            }
+            audio_clip.id = None;
+            audio_clip.name = new_name.clone();
+            audio_clip.playback_position = 0;
+            db.create(&mut audio_clip)?;
+            println!("Saved clip '{}'.", new_name);
         }
         Commands::Delete { name } => {
             db.delete(name)?;
-            println!("Deleted clip '{}' successfully.", name);
+            println!("Moved clip '{}' to the trash. It will be permanently deleted after {} days.", name, db::TRASH_RETENTION_DAYS);
+        }
+        Commands::Rename { name, new_name } => {
+            db.rename(name, new_name)?;
+            println!("Renamed clip '{}' to '{}'.", name, new_name);
+        }
+        Commands::Duplicate { name, new_name } => {
+            db.duplicate(name, new_name)?;
+            println!("Copied clip '{}' to '{}'.", name, new_name);
+        }
+        Commands::Edit { name, operation } => {
+            let mut audio_clip = db.load(name)?;
+            match operation {
+                EditOperation::Trim { start, end } => {
+                    audio_clip.add_edit(Edit::Trim { start: *start, end: *end })?;
+                }
+                EditOperation::Crop { start, end } => {
+                    audio_clip.add_edit(Edit::Crop { start: *start, end: *end })?;
+                }
+                EditOperation::Split { at, name: new_name } => {
+                    let duration = audio_clip.duration()?;
+                    let mut second = audio_clip.clone();
+                    audio_clip.add_edit(Edit::Crop { start: 0.0, end: *at })?;
+                    second.add_edit(Edit::Crop { start: *at, end: duration })?;
+                    second.id = None;
+                    second.name = match new_name {
+                        Some(new_name) => new_name.clone(),
+                        None => db.unique_name(name)?,
+                    };
+                    // Save both halves together so a failure can't leave the audio after the split in two clips
+                    db.transaction(|db| {
+                        db.update(&audio_clip)?;
+                        db.create(&mut second)
+                    })?;
+                    println!("Saved the audio after {}s as clip '{}'.", at, second.name);
+                }
+                EditOperation::Denoise { reduction, noise_start, noise_end, analysis_only } => {
+                    if *reduction <= 0.0 || *reduction > denoise::MAX_REDUCTION_DB {
+                        return Err(eyre!("The reduction must be between 0 and {} dB", denoise::MAX_REDUCTION_DB));
+                    }
+                    let noise = noise_start.zip(*noise_end);
+                    audio_clip.add_edit(Edit::Denoise { reduction: *reduction, noise, analysis_only: *analysis_only })?;
+                }
+                EditOperation::AutoTrim { padding } => {
+                    if !audio_clip.trim_silence(*padding)? {
+                        println!("No speech detected in clip '{}'.", name);
+                    }
+                }
+                EditOperation::Undo {} => {
+                    if audio_clip.edits.0.pop().is_none() {
+                        println!("Clip '{}' has no edits to undo.", name);
+                    }
+                }
+                EditOperation::Reset {} => {
+                    audio_clip.edits.0.clear();
+                }
+                EditOperation::Show {} => {}
+            }
+            db.update(&audio_clip)?;
+            for (index, edit) in audio_clip.edits.0.iter().enumerate() {
+                println!("{}. {}", index + 1, edit);
+            }
+            println!("Clip '{}' is {:.3}s long ({:.3}s recorded).", name, audio_clip.duration()?, audio_clip.samples.len() as f64 / audio_clip.sample_rate as f64);
+        }
+        Commands::Segments { name } => {
+            let audio_clip = db.load(name)?;
+            let sample_rate = audio_clip.sample_rate as f64;
+            let segments = audio_clip.speech_segments()?;
+            let mut speech = 0.0;
+            for segment in &segments {
+                let (start, end) = (segment.start as f64 / sample_rate, segment.end as f64 / sample_rate);
+                speech += end - start;
+                println!("{:8.3}s - {:8.3}s ({:.3}s)", start, end, end - start);
+            }
+            println!("{} speech segment(s), {:.3}s of speech in {:.3}s.", segments.len(), speech, audio_clip.duration()?);
         }
+        Commands::Export { name, output, normalize, processing } => {
+            let mut audio_clip = db.load(name)?;
+            if let Some(target) = normalize {
+                apply_normalization(&mut audio_clip, *target)?;
+            }
+            let audio_clip = processing.apply(&db, audio_clip)?.audio_clip;
+            let output = output.clone().unwrap_or_else(|| PathBuf::from(format!("{}.wav", name)));
+            audio_clip.export_wav(&output)?;
+            println!("Exported clip '{}' to {}.", name, output.display());
+        }
+        Commands::Tracks { name, format, output, recorded } => {
+            let audio_clip = db.load(name)?;
+            let speech = audio_clip.speech()?;
+            let analysis = Analysis::of_speech(&audio_clip, &speech);
+            let output = output.clone().unwrap_or_else(|| PathBuf::from(format!("{}.{}", name, format.extension())));
+            tracks::export(&audio_clip, &speech, &analysis, *format, *recorded, &output)?;
+            println!("Exported {} voiced frame(s) of clip '{}' to {}.", analysis.frames.len(), name, output.display());
+        }
+        Commands::Analyze { name } => {
+            let audio_clip = db.load(name)?;
+            let analysis = Analysis::of(&audio_clip)?;
+            println!("Voiced time: {:.2}s of {:.2}s", analysis.voiced_time(), audio_clip.duration()?);
+            print_medians(&db, &audio_clip, &analysis, &Metric::ALL)?;
+            goals::report(&db, &audio_clip, &analysis)?;
+        }
+        Commands::Compare { a, b, play, reference } => {
+            let (a_clip, b_clip) = (db.load(a)?, db.load(b)?);
+            let (a_speech, b_speech) = (a_clip.speech()?, b_clip.speech()?);
+            let alignment = compare::Alignment::of(&a_clip, &a_speech, &b_clip, &b_speech)?;
+            let (a_analysis, b_analysis) = (Analysis::of_speech(&a_clip, &a_speech), Analysis::of_speech(&b_clip, &b_speech));
+            let comparisons = compare::compare_segments(&a_clip, &a_speech, &a_analysis, &b_analysis, &alignment);
+            println!("Aligned '{}' ({:.2}s) with '{}' ({:.2}s), mean frame distance {:.1}", a, a_clip.duration()?, b, b_clip.duration()?, alignment.cost);
+            println!("{:<8} {:<16} {:<16} {:>9} {:>9} {:>9}", "Segment", a, b, "Pitch", "F1", "F2");
+            let show = |value: Option<f32>, unit: &str| value.map_or("-".to_string(), |value| format!("{:+.1} {}", value, unit));
+            for (index, comparison) in comparisons.iter().enumerate() {
+                println!(
+                    "{:<8} {:<16} {:<16} {:>9} {:>9} {:>9}",
+                    index + 1,
+                    format!("{:.2}-{:.2}s", comparison.a.0, comparison.a.1),
+                    format!("{:.2}-{:.2}s", comparison.b.0, comparison.b.1),
+                    show(comparison.pitch, "st"),
+                    show(comparison.f1, "Hz"),
+                    show(comparison.f2, "Hz"),
+                );
+            }
+            if let Some(reference) = reference {
+                let target = load_reference(&db, reference)?;
+                for (audio_clip, analysis) in [(&a_clip, a_analysis), (&b_clip, b_analysis)] {
+                    let profile = reference::Profile::of(&db, &mut LazyAnalysis::with(audio_clip, analysis))?;
+                    let differences: Vec<String> = profile.differences(&target).iter()
+                        .map(|(metric, difference)| format!("{} {:+.1} {}", metric, difference, if metric.is_frequency() { "st" } else { metric.unit() }))
+                        .collect();
+                    match profile.distance(&target) {
+                        Some(distance) => println!("Distance of '{}' from '{}': {:.2} ({})", audio_clip.name, reference, distance, differences.join(", ")),
+                        None => println!("Distance of '{}' from '{}': -", audio_clip.name, reference),
+                    }
+                }
+            }
+            if *play {
+                for (index, comparison) in comparisons.iter().enumerate() {
+                    for (audio_clip, (start, end)) in [(&a_clip, comparison.a), (&b_clip, comparison.b)] {
+                        println!("Segment {}: '{}' {:.2}-{:.2}s", index + 1, audio_clip.name, start, end);
+                        let mut segment = audio_clip.clone();
+                        segment.add_edit(Edit::Crop { start, end })?;
+                        segment.play()?;
+                    }
+                }
+            }
+        }
+        Commands::Events { name } => {
+            let audio_clip = db.load(name)?;
+            let speech = audio_clip.speech()?;
+            let analysis = Analysis::of_speech(&audio_clip, &speech);
+            let events = events::detect(&audio_clip, &speech, &analysis);
+            for event in &events {
+                println!("{}", event);
+            }
+            match events.first() {
+                Some(first) => println!(
+                    "{} event(s). Replay one with e.g. `oxygen play {} --start {:.2}`",
+                    events.len(), name, (first.start - EVENT_LEAD_IN).max(0.0),
+                ),
+                None => println!("No creak or pitch breaks found."),
+            }
+            goals::report(&db, &audio_clip, &analysis)?;
+        }
+        Commands::Prosody { name } => {
+            let audio_clip = db.load(name)?;
+            let speech = audio_clip.speech()?;
+            let analysis = Analysis::of_speech(&audio_clip, &speech);
+            print_prosody(&prosody::Prosody::of(&audio_clip, &speech, &analysis));
+            goals::report(&db, &audio_clip, &analysis)?;
+        }
+        Commands::Quality { name } => {
+            let audio_clip = db.load(name)?;
+            let speech = audio_clip.speech()?;
+            if !print_quality(&audio_clip, &speech) {
+                return Err(eyre!("Clip '{}' has too little voiced audio to measure", name));
+            }
+            goals::report(&db, &audio_clip, &Analysis::of_speech(&audio_clip, &speech))?;
+        }
+        Commands::Glide { name: Some(name) } => {
+            let audio_clip = db.load(name)?;
+            let Some(score) = glide::score(&db, &mut LazyAnalysis::new(&audio_clip))? else {
+                return Err(eyre!("Clip '{}' has too little voiced audio to score", name));
+            };
+            print_glide(&score);
+        }
+        Commands::Glide { name: None } => {
+            for (name, created_at, score) in db.glide_scores()? {
+                println!(
+                    "{} {:<24} | range {:5.1} st | smoothness {:5.2} st | breaks {} | dropouts {}",
+                    created_at.format("%Y-%m-%d %H:%M"), name, score.coverage, score.smoothness, score.breaks, score.dropouts,
+                );
+            }
+        }
+        Commands::Resonance { name, tracks } => {
+            let audio_clip = db.load(name)?;
+            let analysis = Analysis::of(&audio_clip)?;
+            print_medians(&db, &audio_clip, &analysis, &[Metric::Centroid, Metric::F2, Metric::Vtl, Metric::Brightness])?;
+            if let Some(path) = tracks {
+                let mut file = std::fs::File::create(path)?;
+                tracks::write_csv(&analysis, &resonance::TRACK_METRICS, 0.0, &mut file)?;
+                println!("Wrote {} frame(s) to {}.", analysis.frames.len(), path.display());
+            }
+            goals::report(&db, &audio_clip, &analysis)?;
+        }
+        Commands::Range { names, output } => {
+            let audio_clips = if names.is_empty() {
+                // Reference clips are other voices, so they stay out of the user's own range
+                let mut audio_clips = db.list()?;
+                audio_clips.retain(|audio_clip| !audio_clip.reference);
+                audio_clips
+            } else {
+                names.iter().map(|name| db.load(name)).collect::<Result<Vec<_>>>()?
+            };
+            let mut profile = range::RangeProfile::default();
+            for audio_clip in &audio_clips {
+                profile.add(&Analysis::of(audio_clip)?);
+            }
+            let (Some(lowest), Some(highest)) = (profile.lowest(), profile.highest()) else {
+                return Err(eyre!("No note was sustained for {}s in the selected clips", range::SUSTAINED_TIME));
+            };
+            for (note, levels) in profile.sustained() {
+                println!(
+                    "{:<4} {:6.1} to {:6.1} dBFS ({:4.1} dB range, {:.2}s)",
+                    pitch::midi_name(note), levels.softest(), levels.loudest(), levels.dynamic_range(), levels.time(),
+                );
+            }
+            println!("Lowest sustained note: {}", pitch::midi_name(lowest));
+            println!("Highest sustained note: {} ({} semitones)", pitch::midi_name(highest), highest - lowest);
+            std::fs::write(output, profile.to_svg())?;
+            println!("Wrote the voice range profile to {}.", output.display());
+        }
+        Commands::Progress { goal, reference } => {
+            let goals = match goal {
+                Some(goal) => vec![db.load_goal(goal)?],
+                None => db.goals()?,
+            };
+            let target = reference.as_ref().map(|reference| load_reference(&db, reference)).transpose()?;
+            let mut audio_clips = db.list()?;
+            audio_clips.retain(|audio_clip| !audio_clip.reference);
+            audio_clips.reverse();
+            for audio_clip in &audio_clips {
+                let mut analysis = LazyAnalysis::new(audio_clip);
+                let mut line = format!("{} {:<24}", audio_clip.created_at.format("%Y-%m-%d %H:%M"), audio_clip.name);
+                for metric in PROGRESS_METRICS {
+                    let median = goals::median(&db, &mut analysis, metric)?;
+                    let median = median.map_or("-".to_string(), |median| format!("{:.1} {}", median, metric.unit()));
+                    line.push_str(&format!(" | {} {:>8}", metric, median));
+                }
+                for goal in &goals {
+                    let score = goals::score(&db, &mut analysis, goal)?;
+                    let score = score.map_or("-".to_string(), |score| format!("{:.1}%", score * 100.0));
+                    line.push_str(&format!(" | {} {:>6}", goal.name, score));
+                }
+                if let Some(target) = &target {
+                    let distance = reference::Profile::of(&db, &mut analysis)?.distance(target);
+                    line.push_str(&format!(" | distance {:>5}", distance.map_or("-".to_string(), |distance| format!("{:.2}", distance))));
+                }
+                println!("{}", line);
+            }
+        }
+        Commands::Import { path, name, reference } => {
+            let name = match name {
+                Some(name) => name.clone(),
+                None => path.file_stem().ok_or(eyre!("Cannot name a clip after {}", path.display()))?.to_string_lossy().into_owned(),
+            };
+            let mut audio_clip = AudioClip::import_wav(path, name)?;
+            audio_clip.reference = *reference;
+            db.create(&mut audio_clip)?;
+            let kind = if audio_clip.reference { "reference clip" } else { "clip" };
+            println!("Imported {} as {} '{}' ({:.2}s).", path.display(), kind, audio_clip.name, audio_clip.duration()?);
+        }
+        Commands::Reference { command } => match command {
+            ReferenceCommands::Add { name } => {
+                let mut audio_clip = db.load(name)?;
+                audio_clip.reference = true;
+                db.update(&audio_clip)?;
+                println!("Clip '{}' is now a reference.", name);
+            }
+            ReferenceCommands::List {} => {
+                for audio_clip in db.list()?.iter().filter(|audio_clip| audio_clip.reference) {
+                    let mut analysis = LazyAnalysis::new(audio_clip);
+                    let mut line = audio_clip.name.clone();
+                    for (metric, _) in reference::PROFILE {
+                        let median = goals::median(&db, &mut analysis, metric)?;
+                        line.push_str(&format!(" | {} {}", metric, median.map_or("-".to_string(), |median| format!("{:.1} {}", median, metric.unit()))));
+                    }
+                    println!("{}", line);
+                }
+            }
+            ReferenceCommands::Remove { name } => {
+                let mut audio_clip = db.load(name)?;
+                audio_clip.reference = false;
+                db.update(&audio_clip)?;
+                println!("Clip '{}' is no longer a reference.", name);
+            }
+        },
+        Commands::Practice { exercise } => {
+            let exercise = db.load_exercise(exercise)?;
+            println!("{} ({}, {} step(s), up to {:.0}s)", exercise.name, exercise.kind, exercise.steps.len(), exercise.duration());
+            if !exercise.description.is_empty() {
+                println!("{}", exercise.description);
+            }
+            let started = Local::now().format("%Y-%m-%d_%H-%M-%S");
+            // Ctrl+C ends the current step early instead of quitting, and ends the session when pressed between steps
+            let running = audio_clips::running_until_ctrl_c()?;
+            let mut recorded = 0;
+            for (i, step) in exercise.steps.iter().enumerate() {
+                println!();
+                println!("Step {}/{}: {} ({:.0}s)", i + 1, exercise.steps.len(), step.prompt, step.duration);
+                print!("Press Enter to start (q to finish) ");
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("q") || !running.load(std::sync::atomic::Ordering::SeqCst) {
+                    break;
+                }
+                let name = db.unique_name(&format!("{}_{}-{}", exercise.name, started, i + 1))?;
+                let mut audio_clip = AudioClip::record_timed(name, step.duration, &running)?;
+                // A Ctrl+C that stopped the recording only ends this step
+                running.store(true, std::sync::atomic::Ordering::SeqCst);
+                audio_clip.exercise = Some(exercise.name.clone());
+                db.create(&mut audio_clip)?;
+                recorded += 1;
+                println!("Saved clip '{}' ({:.2}s).", audio_clip.name, audio_clip.duration()?);
+
+                let speech = audio_clip.speech()?;
+                let analysis = Analysis::of_speech(&audio_clip, &speech);
+                print_medians(&db, &audio_clip, &analysis, practice_metrics(exercise.kind))?;
+                match exercise.kind {
+                    ExerciseKind::Vowel => {
+                        if !print_quality(&audio_clip, &speech) {
+                            println!("Too little voiced audio to measure voice quality.");
+                        }
+                    }
+                    ExerciseKind::Glide => match glide::score(&db, &mut LazyAnalysis::new(&audio_clip))? {
+                        Some(score) => print_glide(&score),
+                        None => println!("Too little voiced audio to score the glide."),
+                    },
+                    ExerciseKind::Reading => print_prosody(&prosody::Prosody::of(&audio_clip, &speech, &analysis)),
+                }
+                goals::report(&db, &audio_clip, &analysis)?;
+            }
+            println!();
+            println!("Recorded {} of {} step(s) of '{}'.", recorded, exercise.steps.len(), exercise.name);
+        }
+        Commands::Stats { weeks } => {
+            let stats = stats::PracticeStats::of(&db.list()?)?;
+            let today = Local::now().date_naive();
+            println!("Recorded: {} in {} clip(s), {} session(s) on {} day(s)", stats::format_time(stats.total_time), stats.clips, stats.sessions.len(), stats.days.len());
+            println!("Current streak: {} day(s), longest: {} day(s)", stats.current_streak(today), stats.longest_streak());
+            println!();
+            println!("Sessions per week:");
+            for (week, sessions) in stats.sessions_per_week(*weeks, today) {
+                println!("  {} {:>3} {}", week.format("%Y-%m-%d"), sessions, "#".repeat(sessions));
+            }
+            println!();
+            println!("Time per exercise:");
+            let mut exercises: Vec<_> = stats.exercises.iter().collect();
+            exercises.sort_by(|a, b| b.1.total_cmp(a.1));
+            for (exercise, time) in exercises {
+                println!("  {:<24} {:>8}", exercise, stats::format_time(*time));
+            }
+        }
+        Commands::Report { from, to, output, notes, clips } => {
+            let to = to.unwrap_or_else(|| Local::now().date_naive());
+            let from = from.unwrap_or(to - Days::new(6));
+            if from > to {
+                return Err(eyre!("The report cannot start after it ends"));
+            }
+            let mut audio_clips = db.list()?;
+            audio_clips.retain(|audio_clip| {
+                let day = audio_clip.created_at.with_timezone(&Local).date_naive();
+                !audio_clip.reference && from <= day && day <= to
+            });
+            let report = report::Report {
+                from,
+                to,
+                audio_clips,
+                metrics: &PROGRESS_METRICS,
+                goals: db.goals()?,
+                notes: notes.clone(),
+                snippets: clips.iter().map(|name| db.load(name)).collect::<Result<_>>()?,
+            };
+            std::fs::write(output, report.to_html(&db)?)?;
+            println!("Wrote the report for {} to {} to {}.", from, to, output.display());
+        }
+        Commands::Reminder { min_minutes, quiet } => {
+            let stats = stats::PracticeStats::of(&db.list()?)?;
+            let today = Local::now().date_naive();
+            let time = stats.time_on(today);
+            let done = time > 0.0 && time >= min_minutes * 60.0;
+            if !quiet {
+                if done {
+                    println!("Practice done today ({}), streak: {} day(s).", stats::format_time(time), stats.current_streak(today));
+                } else {
+                    println!(
+                        "Practice not done yet today ({} of {:.0} minute(s)), streak: {} day(s).",
+                        stats::format_time(time), min_minutes, stats.current_streak(today),
+                    );
+                }
+            }
+            if !done {
+                std::process::exit(1);
+            }
+        }
+        Commands::Exercise { command } => match command {
+            ExerciseCommands::List {} => {
+                for exercise in db.exercises()? {
+                    println!("{} ({}, {} step(s), {:.0}s) {}", exercise.name, exercise.kind, exercise.steps.len(), exercise.duration(), exercise.description);
+                }
+            }
+            ExerciseCommands::Show { name } => {
+                let exercise = db.load_exercise(name)?;
+                println!("{} ({})", exercise.name, exercise.kind);
+                if !exercise.description.is_empty() {
+                    println!("{}", exercise.description);
+                }
+                for (i, step) in exercise.steps.iter().enumerate() {
+                    println!("{:>3}. {} ({:.0}s)", i + 1, step.prompt, step.duration);
+                }
+            }
+            ExerciseCommands::Add { name, kind, description, prompts, duration } => {
+                if *duration <= 0.0 {
+                    return Err(eyre!("The step duration must be positive"));
+                }
+                let steps = prompts.iter().map(|prompt| Step { prompt: prompt.clone(), duration: *duration }).collect();
+                let mut exercise = Exercise { id: None, name: name.clone(), kind: *kind, description: description.clone(), steps };
+                db.create_exercise(&mut exercise)?;
+                println!("Added exercise '{}' with {} step(s).", exercise.name, exercise.steps.len());
+            }
+            ExerciseCommands::Remove { name } => {
+                db.delete_exercise(name)?;
+                println!("Removed exercise '{}'.", name);
+            }
+        },
+        Commands::Fx { command } => match command {
+            FxCommands::Set { chain } => {
+                if chain.is_empty() {
+                    return Err(eyre!("The chain is empty, use `fx clear` to remove the default"));
+                }
+                db.set_setting(effects::FX_SETTING, &chain.to_string())?;
+                println!("Default effect chain set to {}.", chain);
+            }
+            FxCommands::Show {} => match db.setting(effects::FX_SETTING)? {
+                Some(chain) => println!("{}", chain),
+                None => println!("No default effect chain."),
+            },
+            FxCommands::Clear {} => {
+                if db.delete_setting(effects::FX_SETTING)? {
+                    println!("Removed the default effect chain.");
+                } else {
+                    println!("No default effect chain.");
+                }
+            }
+            FxCommands::List {} => {
+                for (syntax, description) in effects::EFFECT_HELP {
+                    println!("{:<26} {}", syntax, description);
+                }
+            }
+        },
+        Commands::Goal { command } => match command {
+            GoalCommands::Add { name, metric, min, max } => {
+                if min >= max {
+                    return Err(eyre!("The minimum must be below the maximum"));
+                }
+                let mut goal = Goal { id: None, name: name.clone(), metric: *metric, min: *min, max: *max };
+                db.create_goal(&mut goal)?;
+                println!("Added goal {}.", goal.describe());
+            }
+            GoalCommands::List {} => {
+                for goal in db.goals()? {
+                    println!("{}", goal.describe());
+                }
+            }
+            GoalCommands::Remove { name } => {
+                db.delete_goal(name)?;
+                println!("Removed goal '{}'.", name);
+            }
+        },
+        Commands::Loudness { name } => {
+            let loudness = db.load(name)?.loudness()?;
+            println!("Integrated loudness: {:.1} LUFS", loudness.integrated);
+            let lufs = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.1} LUFS", value));
+            println!("Max short-term loudness: {}", lufs(loudness.max_short_term()));
+            println!("Max momentary loudness: {}", lufs(loudness.max_momentary()));
+            println!("True peak: {:.1} dBTP", loudness.true_peak);
+        }
+        Commands::Trash { command } => match command {
+            TrashCommands::List {} => {
+                for entry in db.trash_list()? {
+                    println!("{} {}", entry.name, entry.deleted_at);
+                }
+            }
+            TrashCommands::Restore { name } => {
+                db.trash_restore(name)?;
+                println!("Restored clip '{}'.", name);
+            }
+            TrashCommands::Empty { older_than } => {
+                let before = older_than.map(|days| Utc::now() - Duration::days(days));
+                let removed = db.trash_empty(before)?;
+                println!("Permanently deleted {} clip(s).", removed);
+            }
+        },
     }   
     
     println!("{:?}",cli);
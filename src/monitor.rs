@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// Live pitch feedback drawn in the terminal while speaking
+use color_eyre::eyre::{Result, eyre};
+use crate::audio_clips::{self, AudioClip};
+use crate::pitch::{self, PitchTracker};
+
+/// How often the pitch is estimated and the trace redrawn
+const HOP: Duration = Duration::from_millis(40);
+/// Columns of pitch history shown
+const WIDTH: usize = 72;
+/// Rows of the pitch axis
+const HEIGHT: usize = 18;
+/// Lowest and highest pitch shown, in Hz
+const DISPLAY_RANGE: (f32, f32) = (70.0, 500.0);
+
+/// The outcome of a monitoring session
+pub struct Session {
+    /// The recorded audio, if it was kept
+    pub audio_clip: Option<AudioClip>,
+    /// Share of the voiced time spent inside the target band
+    pub in_target: Option<f32>,
+}
+
+/// Show a scrolling pitch trace until Ctrl+C is pressed. The audio is returned when `keep` is set
+pub fn run(name: String, target: Option<(f32, f32)>, keep: bool) -> Result<Session> {
+    let (stream, clip) = AudioClip::start_recording(name)?;
+    let running = audio_clips::running_until_ctrl_c()?;
+    let sample_rate = clip.lock().unwrap().as_ref().map(|clip| clip.sample_rate).ok_or(eyre!("Recording is not running"))?;
+
+    // The worker estimates the pitch of the newest frame and hands it to the drawing loop
+    let (sender, receiver) = mpsc::channel();
+    let worker = {
+        let clip = clip.clone();
+        let running = running.clone();
+        std::thread::spawn(move || {
+            let tracker = PitchTracker::new(sample_rate);
+            let frame_len = tracker.frame_len();
+            while running.load(Ordering::SeqCst) {
+                std::thread::sleep(HOP);
+                let frame = {
+                    let mut guard = clip.lock().unwrap();
+                    let Some(clip) = guard.as_mut() else { return Err(eyre!("Recording was already finished")) };
+                    if clip.samples.len() < frame_len {
+                        continue;
+                    }
+                    let frame = clip.samples[clip.samples.len() - frame_len..].to_vec();
+                    // Without a recording only the newest frame is needed
+                    if !keep {
+                        let old = clip.samples.len() - frame_len;
+                        clip.samples.drain(..old);
+                    }
+                    frame
+                };
+                if sender.send(tracker.estimate(&frame).0).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        })
+    };
+
+    print!("\x1b[2J\x1b[?25l");
+    let mut history: VecDeque<Option<f32>> = VecDeque::with_capacity(WIDTH);
+    let (mut voiced, mut inside) = (0usize, 0usize);
+    while running.load(Ordering::SeqCst) {
+        // The worker only hangs up when it stops early, and joining it below reports why
+        let f0 = match receiver.recv_timeout(HOP * 5) {
+            Ok(f0) => f0,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if history.len() == WIDTH {
+            history.pop_front();
+        }
+        history.push_back(f0);
+        if let Some(f0) = f0 {
+            voiced += 1;
+            inside += usize::from(in_band(f0, target));
+        }
+        let in_target = (voiced > 0).then(|| inside as f32 / voiced as f32);
+        print!("\x1b[H{}", draw(&history, target, in_target));
+    }
+    println!("\x1b[?25h");
+
+    worker.join().map_err(|_| eyre!("Pitch worker panicked"))??;
+    let clip = AudioClip::finish_recording(stream, &clip)?;
+    Ok(Session {
+        audio_clip: keep.then_some(clip),
+        in_target: (target.is_some() && voiced > 0).then(|| inside as f32 / voiced as f32),
+    })
+}
+
+fn in_band(f0: f32, target: Option<(f32, f32)>) -> bool {
+    target.is_some_and(|(min, max)| (min..=max).contains(&f0))
+}
+
+// Render the pitch history with the target band shaded
+fn draw(history: &VecDeque<Option<f32>>, target: Option<(f32, f32)>, in_target: Option<f32>) -> String {
+    let (low, high) = DISPLAY_RANGE;
+    let row_pitch = |row: usize| high * (low / high).powf(row as f32 / (HEIGHT - 1) as f32);
+    let pitch_row = |f0: f32| {
+        let position = (high / f0.clamp(low, high)).ln() / (high / low).ln();
+        (position * (HEIGHT - 1) as f32).round() as usize
+    };
+
+    let mut screen = String::new();
+    for row in 0..HEIGHT {
+        let _ = write!(screen, "{:>5.0} Hz |", row_pitch(row));
+        let band = target.is_some_and(|(min, max)| (pitch_row(max)..=pitch_row(min)).contains(&row));
+        for column in 0..WIDTH {
+            let f0 = history.get(column).copied().flatten();
+            match f0 {
+                Some(f0) if pitch_row(f0) == row && in_band(f0, target) => screen.push_str("\x1b[32m●\x1b[0m"),
+                Some(f0) if pitch_row(f0) == row => screen.push_str("\x1b[33m●\x1b[0m"),
+                _ if band => screen.push_str("\x1b[2m░\x1b[0m"),
+                _ => screen.push(' '),
+            }
+        }
+        screen.push_str("\x1b[K\n");
+    }
+
+    let mut status = match history.back().copied().flatten() {
+        Some(f0) if target.is_none() => format!("{:6.1} Hz {}", f0, pitch::note_name(f0)),
+        Some(f0) if in_band(f0, target) => format!("{:6.1} Hz {} - in target", f0, pitch::note_name(f0)),
+        Some(f0) => format!("{:6.1} Hz {} - outside target", f0, pitch::note_name(f0)),
+        None => "     -".to_string(),
+    };
+    if let (Some(_), Some(in_target)) = (target, in_target) {
+        let _ = write!(status, " | {:.0}% of voiced time in target", in_target * 100.0);
+    }
+    let _ = write!(screen, "{}\x1b[K\nPress Ctrl+C to stop\x1b[K\n", status);
+    screen
+}
+
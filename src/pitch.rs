@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+/// Fundamental frequency tracking with the YIN algorithm
+use rustfft::{Fft, FftPlanner};
+use rustfft::num_complex::Complex;
+use crate::dsp;
+
+/// Lowest fundamental frequency the tracker looks for, in Hz
+pub const MIN_F0: f32 = 60.0;
+/// Highest fundamental frequency the tracker looks for, in Hz
+pub const MAX_F0: f32 = 800.0;
+/// Cumulative mean normalised difference below which a dip counts as a period
+const YIN_THRESHOLD: f32 = 0.15;
+/// Frames quieter than this are treated as unvoiced, in dBFS
+const MIN_LEVEL_DB: f32 = -50.0;
+
+/// Pitch estimate for one analysis frame
+#[derive(Debug, Clone, Copy)]
+pub struct PitchFrame {
+    /// Centre of the frame in seconds
+    pub time: f64,
+    /// Fundamental frequency in Hz, or None for unvoiced frames
+    pub f0: Option<f32>,
+    /// RMS level of the frame in dBFS
+    pub level: f32,
+}
+
+/// Reusable YIN pitch estimator for a given sample rate
+pub struct PitchTracker {
+    sample_rate: u32,
+    min_period: usize,
+    max_period: usize,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+}
+
+impl PitchTracker {
+    pub fn new(sample_rate: u32) -> PitchTracker {
+        let max_period = (sample_rate as f32 / MIN_F0).ceil() as usize;
+        let mut planner = FftPlanner::new();
+        PitchTracker {
+            sample_rate,
+            min_period: (sample_rate as f32 / MAX_F0).floor() as usize,
+            max_period,
+            fft: planner.plan_fft_forward(4 * max_period),
+            ifft: planner.plan_fft_inverse(4 * max_period),
+        }
+    }
+
+    /// Number of samples `estimate` needs: two periods of the lowest pitch
+    pub fn frame_len(&self) -> usize {
+        2 * self.max_period
+    }
+
+    /// Estimate the fundamental frequency of a frame of `frame_len` samples, returning it with the frame clarity
+    pub fn estimate(&self, frame: &[f32]) -> (Option<f32>, f32) {
+        match self.candidate(frame) {
+            Some((f0, clarity)) if clarity > 1.0 - YIN_THRESHOLD => (Some(f0), clarity),
+            Some((_, clarity)) => (None, clarity),
+            None => (None, 0.0),
+        }
+    }
+
+    /// The most likely fundamental frequency of a frame and its clarity (0 to 1), even if the frame is too
+    /// aperiodic to count as voiced
+    pub fn candidate(&self, frame: &[f32]) -> Option<(f32, f32)> {
+        if frame.len() < self.frame_len() || dsp::rms_db(frame) < MIN_LEVEL_DB {
+            return None;
+        }
+        let window = self.max_period;
+        let difference = self.difference(&frame[..self.frame_len()]);
+
+        // Cumulative mean normalised difference
+        let mut normalised = vec![1.0; window + 1];
+        let mut running_sum = 0.0;
+        for tau in 1..=window {
+            running_sum += difference[tau];
+            normalised[tau] = if running_sum > 0.0 { difference[tau] * tau as f32 / running_sum } else { 1.0 };
+        }
+
+        // First dip under the threshold, or the global minimum if there is none
+        let search = self.min_period.max(2)..window;
+        let tau = search.clone()
+            .find(|&tau| normalised[tau] < YIN_THRESHOLD && normalised[tau] <= normalised[tau + 1])
+            .or_else(|| search.min_by(|&a, &b| normalised[a].total_cmp(&normalised[b])));
+        let mut tau = tau?;
+        while tau + 1 < window && normalised[tau + 1] < normalised[tau] {
+            tau += 1;
+        }
+        let clarity = (1.0 - normalised[tau]).clamp(0.0, 1.0);
+
+        // Parabolic interpolation around the dip for sub-sample accuracy
+        let (a, b, c) = (normalised[tau - 1], normalised[tau], normalised[tau + 1]);
+        let denominator = a - 2.0 * b + c;
+        let shift = if denominator.abs() > f32::EPSILON { 0.5 * (a - c) / denominator } else { 0.0 };
+        let period = tau as f32 + shift.clamp(-1.0, 1.0);
+        Some((self.sample_rate as f32 / period, clarity))
+    }
+
+    /// Track the pitch of `samples` with frames every `hop` seconds
+    pub fn track(&self, samples: &[f32], hop: f64) -> Vec<PitchFrame> {
+        let hop = ((hop * self.sample_rate as f64) as usize).max(1);
+        let frame_len = self.frame_len();
+        dsp::frames(samples, frame_len, hop)
+            .map(|(start, frame)| {
+                PitchFrame {
+                    time: (start + frame_len / 2) as f64 / self.sample_rate as f64,
+                    f0: self.estimate(frame).0,
+                    level: dsp::rms_db(frame),
+                }
+            })
+            .collect()
+    }
+
+    /// Positions of the glottal pulses (one waveform peak per period, in fractional samples), grouped by voiced stretch
+    pub fn period_marks(&self, samples: &[f32]) -> Vec<Vec<f64>> {
+        const HOP: f64 = 0.01;
+        let frames = self.track(samples, HOP);
+        let hop = HOP * self.sample_rate as f64;
+        let sample_rate = self.sample_rate as f64;
+
+        let mut stretches = Vec::new();
+        for voiced in frames.split(|frame| frame.f0.is_none()).filter(|voiced| !voiced.is_empty()) {
+            let start = ((voiced[0].time * sample_rate - hop / 2.0).max(0.0)) as usize;
+            let end = ((voiced[voiced.len() - 1].time * sample_rate + hop / 2.0) as usize).min(samples.len());
+            let period_at = |position: usize| {
+                let index = ((position as f64 / sample_rate - voiced[0].time) / HOP).round().clamp(0.0, (voiced.len() - 1) as f64);
+                sample_rate / voiced[index as usize].f0.unwrap_or(MIN_F0) as f64
+            };
+
+            // Start at the highest peak of the first period, then find each next period by waveform matching
+            let first_period = period_at(start) as usize;
+            let Some(mut mark) = peak(samples, start, (start + first_period).min(end)) else { continue };
+            let mut marks = vec![mark as f64];
+            loop {
+                let period = period_at(mark);
+                let low = (mark as f64 + 0.8 * period) as usize;
+                let high = ((mark as f64 + 1.2 * period) as usize).min(end);
+                match best_match(samples, mark, period as usize, low, high) {
+                    Some((next, offset)) => {
+                        marks.push(next as f64 + offset);
+                        mark = next;
+                    }
+                    None => break,
+                }
+            }
+            stretches.push(marks);
+        }
+        stretches
+    }
+
+    // YIN difference function d(tau) for tau in 0..=max_period, using an FFT cross-correlation
+    fn difference(&self, frame: &[f32]) -> Vec<f32> {
+        let window = self.max_period;
+        let len = 4 * self.max_period;
+        let mut head: Vec<Complex<f32>> = (0..len)
+            .map(|i| Complex::new(if i < window { frame[i] } else { 0.0 }, 0.0))
+            .collect();
+        let mut whole: Vec<Complex<f32>> = (0..len)
+            .map(|i| Complex::new(frame.get(i).copied().unwrap_or(0.0), 0.0))
+            .collect();
+        self.fft.process(&mut head);
+        self.fft.process(&mut whole);
+        let mut correlation: Vec<Complex<f32>> = head.iter().zip(&whole).map(|(h, w)| h.conj() * w).collect();
+        self.ifft.process(&mut correlation);
+
+        let mut energy = vec![0.0f32; frame.len() + 1];
+        for (i, sample) in frame.iter().enumerate() {
+            energy[i + 1] = energy[i] + sample * sample;
+        }
+        let head_energy = energy[window];
+        (0..=window)
+            .map(|tau| {
+                let shifted_energy = energy[tau + window] - energy[tau];
+                (head_energy + shifted_energy - 2.0 * correlation[tau].re / len as f32).max(0.0)
+            })
+            .collect()
+    }
+}
+
+// Index of the largest sample in start..end
+fn peak(samples: &[f32], start: usize, end: usize) -> Option<usize> {
+    (start..end).max_by(|&a, &b| samples[a].total_cmp(&samples[b]))
+}
+
+// The position in low..high whose surrounding period best matches the period around `mark`,
+// with a sub-sample offset from parabolic interpolation of the correlation
+fn best_match(samples: &[f32], mark: usize, period: usize, low: usize, high: usize) -> Option<(usize, f64)> {
+    let half = period / 2;
+    let reference = samples.get(mark.checked_sub(half)?..mark + half)?;
+    let correlation = |position: usize| -> Option<f64> {
+        let candidate = samples.get(position.checked_sub(half)?..position + half)?;
+        Some(reference.iter().zip(candidate).map(|(a, b)| *a as f64 * *b as f64).sum())
+    };
+    let scores: Vec<(usize, f64)> = (low..high).filter_map(|position| Some((position, correlation(position)?))).collect();
+    let best = (0..scores.len()).max_by(|&a, &b| scores[a].1.total_cmp(&scores[b].1))?;
+    let (position, b) = scores[best];
+    if b <= 0.0 {
+        return None;
+    }
+    let offset = match (best.checked_sub(1).map(|i| scores[i].1), scores.get(best + 1).map(|score| score.1)) {
+        (Some(a), Some(c)) if (a - 2.0 * b + c).abs() > f64::EPSILON => (0.5 * (a - c) / (a - 2.0 * b + c)).clamp(-0.5, 0.5),
+        _ => 0.0,
+    };
+    Some((position, offset))
+}
+
+/// Name of the nearest equal tempered note, e.g. `A3` for 220 Hz
+pub fn note_name(f0: f32) -> String {
+    midi_name(midi_note(f0))
+}
+
+/// MIDI number of the nearest equal tempered note, e.g. 57 for 220 Hz
+pub fn midi_note(f0: f32) -> i32 {
+    (69.0 + 12.0 * (f0 / 440.0).log2()).round() as i32
+}
+
+/// Name of a MIDI note, e.g. `A3` for 57
+pub fn midi_name(note: i32) -> String {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    format!("{}{}", NAMES[note.rem_euclid(12) as usize], note.div_euclid(12) - 1)
+}
+
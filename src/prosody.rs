@@ -0,0 +1,172 @@
+/// Speaking rate, pauses and intonation variability
+use crate::analysis::{Analysis, HOP, Metric};
+use crate::audio_clips::{AudioClip, Speech};
+use crate::dsp;
+
+/// Intensity window length in seconds
+const INTENSITY_WINDOW: f64 = 0.04;
+/// Frames averaged on each side when smoothing the intensity contour
+const INTENSITY_SMOOTHING: usize = 2;
+/// How far the intensity has to dip between two peaks for them to be separate syllables, in dB
+const MIN_DIP: f32 = 2.0;
+/// Pitch movements smaller than this are not counted as rises or falls, in semitones
+const MIN_MOVEMENT: f32 = 2.0;
+
+/// Prosody statistics of a clip
+#[derive(Debug, Clone)]
+pub struct Prosody {
+    /// Estimated number of syllables
+    pub syllables: usize,
+    /// Total length of the speech segments in seconds
+    pub speaking_time: f64,
+    /// Time from the start of the first to the end of the last speech segment in seconds
+    pub total_time: f64,
+    /// Lengths of the silences between speech segments in seconds
+    pub pauses: Vec<f64>,
+    /// Standard deviation of the pitch around its median, in semitones
+    pub pitch_sd: Option<f32>,
+    /// Number of pitch rises of at least `MIN_MOVEMENT` semitones
+    pub rises: usize,
+    /// Number of pitch falls of at least `MIN_MOVEMENT` semitones
+    pub falls: usize,
+}
+
+impl Prosody {
+    pub fn of(audio_clip: &AudioClip, speech: &Speech, analysis: &Analysis) -> Prosody {
+        let Speech { samples, segments } = speech;
+        let sample_rate = audio_clip.sample_rate as f64;
+        let seconds = |sample: usize| sample as f64 / sample_rate;
+
+        let speaking_time = segments.iter().map(|segment| seconds(segment.len())).sum();
+        let total_time = match (segments.first(), segments.last()) {
+            (Some(first), Some(last)) => seconds(last.end - first.start),
+            _ => 0.0,
+        };
+        let pauses = segments.windows(2).map(|pair| seconds(pair[1].start.saturating_sub(pair[0].end))).collect();
+
+        // Syllable nuclei are voiced intensity peaks above the median speech intensity (after de Jong and Wempe, 2009)
+        let len = (INTENSITY_WINDOW * sample_rate) as usize;
+        let hop = (HOP * sample_rate) as usize;
+        let mut syllables = 0;
+        let mut speech_levels = Vec::new();
+        let mut contours = Vec::new();
+        for segment in segments {
+            let levels: Vec<f32> = dsp::frames(&samples[segment.clone()], len, hop).map(|(_, frame)| dsp::rms_db(frame)).collect();
+            let smoothed: Vec<f32> = (0..levels.len())
+                .map(|i| {
+                    let around = &levels[i.saturating_sub(INTENSITY_SMOOTHING)..(i + INTENSITY_SMOOTHING + 1).min(levels.len())];
+                    around.iter().sum::<f32>() / around.len() as f32
+                })
+                .collect();
+            speech_levels.extend_from_slice(&smoothed);
+            contours.push((seconds(segment.start) + INTENSITY_WINDOW / 2.0, smoothed));
+        }
+        speech_levels.sort_by(f32::total_cmp);
+        let threshold = speech_levels.get(speech_levels.len() / 2).copied().unwrap_or(f32::MAX);
+        let voiced = |time: f64| !analysis.frames_near(time, 2.0 * HOP).is_empty();
+        for (offset, contour) in &contours {
+            // Lowest level since the last syllable; the first peak of a segment has nothing to dip from
+            let mut dip = f32::MIN;
+            for i in 1..contour.len().saturating_sub(1) {
+                dip = dip.min(contour[i]);
+                let is_peak = contour[i] > contour[i - 1] && contour[i] >= contour[i + 1] && contour[i] > threshold;
+                if is_peak && contour[i] - dip >= MIN_DIP && voiced(offset + i as f64 * HOP) {
+                    syllables += 1;
+                    dip = contour[i];
+                }
+            }
+        }
+
+        let (pitch_sd, rises, falls) = intonation(analysis);
+        Prosody { syllables, speaking_time, total_time, pauses, pitch_sd, rises, falls }
+    }
+
+    /// Syllables per second of speech, excluding pauses
+    pub fn articulation_rate(&self) -> Option<f64> {
+        (self.speaking_time > 0.0).then(|| self.syllables as f64 / self.speaking_time)
+    }
+
+    /// Syllables per second from the first to the last speech, including pauses
+    pub fn speech_rate(&self) -> Option<f64> {
+        (self.total_time > 0.0).then(|| self.syllables as f64 / self.total_time)
+    }
+}
+
+// Pitch standard deviation in semitones and the number of rises and falls in the voiced stretches
+fn intonation(analysis: &Analysis) -> (Option<f32>, usize, usize) {
+    let Some(median) = analysis.median(Metric::Pitch) else { return (None, 0, 0) };
+    let semitones: Vec<(f64, f32)> = analysis.frames.iter().map(|frame| (frame.time, 12.0 * (frame.f0 / median).log2())).collect();
+    let mean = semitones.iter().map(|(_, value)| value).sum::<f32>() / semitones.len() as f32;
+    let variance = semitones.iter().map(|(_, value)| (value - mean).powi(2)).sum::<f32>() / semitones.len() as f32;
+
+    // Follow the contour with hysteresis: a movement counts once it reverses from the last extreme by `MIN_MOVEMENT`
+    let (mut rises, mut falls) = (0, 0);
+    let mut stretch_start = 0;
+    for end in 1..=semitones.len() {
+        let continues = end < semitones.len() && semitones[end].0 - semitones[end - 1].0 <= 1.5 * HOP;
+        if continues {
+            continue;
+        }
+        let stretch = &semitones[stretch_start..end];
+        stretch_start = end;
+        let (mut low, mut high) = (stretch[0].1, stretch[0].1);
+        let mut rising: Option<bool> = None;
+        for &(_, value) in &stretch[1..] {
+            match rising {
+                Some(true) if value > high => high = value,
+                Some(true) if high - value >= MIN_MOVEMENT => {
+                    rising = Some(false);
+                    falls += 1;
+                    low = value;
+                }
+                Some(false) if value < low => low = value,
+                Some(false) if value - low >= MIN_MOVEMENT => {
+                    rising = Some(true);
+                    rises += 1;
+                    high = value;
+                }
+                None if value - low >= MIN_MOVEMENT => {
+                    rising = Some(true);
+                    rises += 1;
+                    high = value;
+                }
+                None if high - value >= MIN_MOVEMENT => {
+                    rising = Some(false);
+                    falls += 1;
+                    low = value;
+                }
+                None => {
+                    low = low.min(value);
+                    high = high.max(value);
+                }
+                _ => {}
+            }
+        }
+    }
+    (Some(variance.sqrt()), rises, falls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals;
+
+    #[test]
+    fn close_utterances_give_short_pauses() {
+        let sample_rate = 16000;
+        // Gaps around the voice activity detector's hangover, where neighbouring segments nearly touch
+        for gap_ms in [220, 230, 240] {
+            let mut samples = test_signals::noise(0.5, 0.001, sample_rate, 1);
+            samples.extend(test_signals::harmonic(150.0, 1.0, sample_rate));
+            samples.extend(test_signals::noise(gap_ms as f64 / 1000.0, 0.001, sample_rate, 2));
+            samples.extend(test_signals::harmonic(150.0, 1.0, sample_rate));
+            samples.extend(test_signals::noise(0.5, 0.001, sample_rate, 3));
+            let audio_clip = test_signals::clip(samples, sample_rate);
+
+            let speech = audio_clip.speech().unwrap();
+            let prosody = Prosody::of(&audio_clip, &speech, &Analysis::of_speech(&audio_clip, &speech));
+            assert!(prosody.pauses.iter().all(|pause| (0.0..=gap_ms as f64 / 1000.0).contains(pause)), "{} ms gap: {:?}", gap_ms, prosody.pauses);
+            assert!(prosody.speaking_time <= prosody.total_time);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+/// Voice quality measures (jitter, shimmer and harmonics-to-noise ratio) for sustained vowels
+use crate::audio_clips::{AudioClip, Speech};
+use crate::pitch::{PitchTracker, MAX_F0, MIN_F0};
+
+/// Neighbouring periods differing by more than this factor are not compared (as in Praat)
+const MAX_PERIOD_FACTOR: f64 = 1.3;
+/// How far around a period mark its peak is looked for, as a fraction of the period
+const PEAK_SLACK: f64 = 0.1;
+/// Periods averaged on each side for the amplitude perturbation quotient
+const APQ_HALF_WIDTH: usize = 5;
+
+/// Perturbation and noise measures of the voiced parts of a clip
+#[derive(Debug, Clone, Copy)]
+pub struct VoiceQuality {
+    /// Mean absolute difference of consecutive periods relative to the mean period
+    pub jitter_local: f64,
+    /// Relative average perturbation: deviation of each period from the mean of it and its neighbours
+    pub jitter_rap: f64,
+    /// Mean absolute difference of consecutive period amplitudes relative to the mean amplitude
+    pub shimmer_local: f64,
+    /// Eleven point amplitude perturbation quotient
+    pub shimmer_apq11: f64,
+    /// Harmonics-to-noise ratio in dB
+    pub hnr: f64,
+    /// Number of periods measured
+    pub periods: usize,
+}
+
+impl VoiceQuality {
+    /// Measure the speech of a clip, or None if it has too few periods
+    pub fn of(audio_clip: &AudioClip, speech: &Speech) -> Option<VoiceQuality> {
+        let sample_rate = audio_clip.sample_rate as f64;
+        let tracker = PitchTracker::new(audio_clip.sample_rate);
+
+        let mut jitter = Perturbation::default();
+        let mut rap = Perturbation::default();
+        let mut shimmer = Perturbation::default();
+        let mut apq = Perturbation::default();
+        let mut hnr = Vec::new();
+        for segment in &speech.segments {
+            let segment = &speech.samples[segment.clone()];
+            for marks in tracker.period_marks(segment) {
+                let periods: Vec<f64> = marks.windows(2).map(|pair| (pair[1] - pair[0]) / sample_rate).collect();
+                // Peak amplitude of each period. The marks sit on waveform peaks, so only a little slack is needed;
+                // a window running up to the next mark would pick up the next period's peak as well
+                let amplitudes: Vec<f64> = marks.windows(2)
+                    .map(|pair| {
+                        let slack = ((pair[1] - pair[0]) * PEAK_SLACK) as usize;
+                        let mark = pair[0].round() as usize;
+                        let around = &segment[mark.saturating_sub(slack)..(mark + slack + 1).min(segment.len())];
+                        around.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs())) as f64
+                    })
+                    .collect();
+                let valid = |i: usize| {
+                    let period = periods[i];
+                    (1.0 / MAX_F0 as f64..=1.0 / MIN_F0 as f64).contains(&period)
+                };
+                let comparable = |a: f64, b: f64| a.max(b) / a.min(b) <= MAX_PERIOD_FACTOR;
+
+                for i in 1..periods.len() {
+                    if valid(i) && valid(i - 1) && comparable(periods[i], periods[i - 1]) {
+                        jitter.add((periods[i] - periods[i - 1]).abs(), periods[i]);
+                        shimmer.add((amplitudes[i] - amplitudes[i - 1]).abs(), amplitudes[i]);
+                    }
+                }
+                for i in 1..periods.len().saturating_sub(1) {
+                    let neighbours = [periods[i - 1], periods[i], periods[i + 1]];
+                    if (i - 1..=i + 1).all(valid) && comparable(neighbours[0], neighbours[1]) && comparable(neighbours[1], neighbours[2]) {
+                        let mean = neighbours.iter().sum::<f64>() / 3.0;
+                        rap.add((periods[i] - mean).abs(), periods[i]);
+                    }
+                }
+                for i in APQ_HALF_WIDTH..periods.len().saturating_sub(APQ_HALF_WIDTH) {
+                    let around = &amplitudes[i - APQ_HALF_WIDTH..=i + APQ_HALF_WIDTH];
+                    if (i - APQ_HALF_WIDTH..=i + APQ_HALF_WIDTH).all(valid) {
+                        let mean = around.iter().sum::<f64>() / around.len() as f64;
+                        apq.add((amplitudes[i] - mean).abs(), amplitudes[i]);
+                    }
+                }
+                for pair in marks.windows(3) {
+                    let period = (pair[1] - pair[0]).round() as usize;
+                    if let Some(harmonicity) = harmonicity(segment, pair[0] as usize, period) {
+                        hnr.push(10.0 * (harmonicity / (1.0 - harmonicity)).log10());
+                    }
+                }
+            }
+        }
+
+        if jitter.count < 2 || hnr.is_empty() {
+            return None;
+        }
+        Some(VoiceQuality {
+            jitter_local: jitter.ratio(),
+            jitter_rap: rap.ratio(),
+            shimmer_local: shimmer.ratio(),
+            shimmer_apq11: apq.ratio(),
+            hnr: hnr.iter().sum::<f64>() / hnr.len() as f64,
+            periods: jitter.count + 1,
+        })
+    }
+}
+
+// Running mean of perturbations relative to the running mean of the values
+#[derive(Default)]
+struct Perturbation {
+    differences: f64,
+    values: f64,
+    count: usize,
+}
+
+impl Perturbation {
+    fn add(&mut self, difference: f64, value: f64) {
+        self.differences += difference;
+        self.values += value;
+        self.count += 1;
+    }
+
+    fn ratio(&self) -> f64 {
+        if self.values > 0.0 { self.differences / self.values } else { f64::NAN }
+    }
+}
+
+// Normalised correlation between one period and the next, clamped just below 1
+fn harmonicity(samples: &[f32], start: usize, period: usize) -> Option<f64> {
+    // Allow a couple of samples of slack so the best alignment is found
+    (period.saturating_sub(2)..=period + 2)
+        .filter_map(|lag| {
+            let first = samples.get(start..start + period)?;
+            let second = samples.get(start + lag..start + lag + period)?;
+            let cross: f64 = first.iter().zip(second).map(|(a, b)| *a as f64 * *b as f64).sum();
+            let energy: f64 = first.iter().map(|a| (*a as f64).powi(2)).sum::<f64>()
+                * second.iter().map(|b| (*b as f64).powi(2)).sum::<f64>();
+            (energy > 0.0).then(|| cross / energy.sqrt())
+        })
+        .max_by(f64::total_cmp)
+        .filter(|r| *r > 0.0)
+        .map(|r| r.min(1.0 - 1e-9))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const PERIOD: f64 = 1.0 / 150.0;
+    const CYCLES: usize = 300;
+
+    // Measure a vowel made of the given cycles, with quiet noise around it so it is detected as speech
+    fn measure(periods: &[f64], amplitudes: &[f64], noise: f64) -> VoiceQuality {
+        let mut samples = test_signals::noise(0.5, 0.0005, SAMPLE_RATE, 1);
+        let voice = test_signals::cycles(periods, amplitudes, SAMPLE_RATE);
+        let noise = test_signals::noise(voice.len() as f64 / SAMPLE_RATE as f64 + 1.0, noise, SAMPLE_RATE, 2);
+        samples.extend(voice.iter().zip(&noise).map(|(voice, noise)| voice + noise));
+        samples.extend(test_signals::noise(0.5, 0.0005, SAMPLE_RATE, 3));
+        let audio_clip = test_signals::clip(samples, SAMPLE_RATE);
+        VoiceQuality::of(&audio_clip, &audio_clip.speech().unwrap()).expect("too few periods measured")
+    }
+
+    #[test]
+    fn jitter_of_alternating_periods() {
+        // Periods alternating 1% above and below the mean differ by 2% of the period each time
+        let periods: Vec<f64> = (0..CYCLES).map(|i| PERIOD * if i % 2 == 0 { 1.01 } else { 0.99 }).collect();
+        let quality = measure(&periods, &[0.3; CYCLES], 0.0);
+        assert!((quality.jitter_local - 0.02).abs() < 0.004, "jitter {}", quality.jitter_local);
+        assert!(quality.shimmer_local < 0.01, "shimmer {}", quality.shimmer_local);
+    }
+
+    #[test]
+    fn shimmer_of_alternating_amplitudes() {
+        // Amplitudes alternating 5% above and below the mean differ by 10% each time
+        let amplitudes: Vec<f64> = (0..CYCLES).map(|i| 0.3 * if i % 2 == 0 { 1.05 } else { 0.95 }).collect();
+        let quality = measure(&[PERIOD; CYCLES], &amplitudes, 0.0);
+        assert!((quality.shimmer_local - 0.1).abs() < 0.02, "shimmer {}", quality.shimmer_local);
+        assert!(quality.jitter_local < 0.002, "jitter {}", quality.jitter_local);
+    }
+
+    #[test]
+    fn hnr_of_added_noise() {
+        // The wave has a mean power of 0.3^2 * (1 + 0.3^2) / 2; noise 20 dB below it
+        let power = 0.09 * 1.09 / 2.0;
+        let quality = measure(&[PERIOD; CYCLES], &[0.3; CYCLES], (power / 100.0f64).sqrt());
+        assert!((quality.hnr - 20.0).abs() < 2.0, "HNR {}", quality.hnr);
+    }
+}
@@ -0,0 +1,124 @@
+/// Voice range profile (phonetogram): the levels a voice produces on each semitone
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::analysis::{Analysis, HOP};
+use crate::pitch;
+
+/// Voiced time on a semitone needed for it to count as sustainable, in seconds
+pub const SUSTAINED_TIME: f64 = 0.25;
+/// Share of frames ignored at each end of a semitone's levels, so onsets and glitches don't stretch its range
+const LEVEL_PERCENTILE: f32 = 0.05;
+/// Chart size and margins in pixels
+const CHART_WIDTH: f32 = 800.0;
+const CHART_HEIGHT: f32 = 400.0;
+const MARGIN: f32 = 50.0;
+
+/// The levels of all voiced frames on one semitone
+#[derive(Debug, Clone, Default)]
+pub struct NoteLevels {
+    levels: Vec<f32>,
+}
+
+impl NoteLevels {
+    /// Voiced time on this semitone in seconds
+    pub fn time(&self) -> f64 {
+        self.levels.len() as f64 * HOP
+    }
+
+    /// Softest level in dBFS
+    pub fn softest(&self) -> f32 {
+        self.percentile(LEVEL_PERCENTILE)
+    }
+
+    /// Loudest level in dBFS
+    pub fn loudest(&self) -> f32 {
+        self.percentile(1.0 - LEVEL_PERCENTILE)
+    }
+
+    pub fn dynamic_range(&self) -> f32 {
+        self.loudest() - self.softest()
+    }
+
+    // Levels are kept sorted, so a percentile is a lookup
+    fn percentile(&self, share: f32) -> f32 {
+        let index = ((self.levels.len() - 1) as f32 * share).round() as usize;
+        self.levels[index]
+    }
+}
+
+/// Levels per MIDI note, combined over any number of clips
+#[derive(Debug, Default)]
+pub struct RangeProfile {
+    notes: BTreeMap<i32, NoteLevels>,
+}
+
+impl RangeProfile {
+    /// Add the voiced frames of an analysed clip
+    pub fn add(&mut self, analysis: &Analysis) {
+        for frame in &analysis.frames {
+            let note = self.notes.entry(pitch::midi_note(frame.f0)).or_default();
+            let index = note.levels.partition_point(|level| *level < frame.level);
+            note.levels.insert(index, frame.level);
+        }
+    }
+
+    /// The semitones voiced for at least `SUSTAINED_TIME`, from low to high
+    pub fn sustained(&self) -> impl Iterator<Item = (i32, &NoteLevels)> {
+        self.notes.iter().filter(|(_, levels)| levels.time() >= SUSTAINED_TIME).map(|(note, levels)| (*note, levels))
+    }
+
+    pub fn lowest(&self) -> Option<i32> {
+        self.sustained().next().map(|(note, _)| note)
+    }
+
+    pub fn highest(&self) -> Option<i32> {
+        self.sustained().last().map(|(note, _)| note)
+    }
+
+    /// Render the sustained semitones as an SVG chart of level against pitch
+    pub fn to_svg(&self) -> String {
+        let notes: Vec<(i32, &NoteLevels)> = self.sustained().collect();
+        let (low, high) = match (notes.first(), notes.last()) {
+            (Some(first), Some(last)) => (first.0 - 2, last.0 + 2),
+            _ => (pitch::midi_note(110.0), pitch::midi_note(440.0)),
+        };
+        let floor = notes.iter().map(|(_, levels)| levels.softest()).fold(-40.0, f32::min);
+        let floor = (floor / 10.0).floor() * 10.0;
+        let ceiling = notes.iter().map(|(_, levels)| levels.loudest()).fold(floor + 10.0, f32::max);
+        let ceiling = (ceiling / 10.0).ceil() * 10.0;
+        let x = |note: i32| MARGIN + (note - low) as f32 / (high - low) as f32 * (CHART_WIDTH - 2.0 * MARGIN);
+        let y = |level: f32| CHART_HEIGHT - MARGIN - (level - floor) / (ceiling - floor) * (CHART_HEIGHT - 2.0 * MARGIN);
+
+        let mut svg = String::new();
+        let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="11">"#, CHART_WIDTH, CHART_HEIGHT);
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+        let _ = writeln!(svg, r#"<text x="{}" y="20" font-size="14" text-anchor="middle">Voice range profile</text>"#, CHART_WIDTH / 2.0);
+        let mut level = floor;
+        while level <= ceiling {
+            let _ = writeln!(svg, r##"<line x1="{}" y1="{y:.1}" x2="{}" y2="{y:.1}" stroke="#ddd"/>"##, MARGIN, CHART_WIDTH - MARGIN, y = y(level));
+            let _ = writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{} dB</text>"#, MARGIN - 5.0, y(level) + 4.0, level);
+            level += 10.0;
+        }
+        for note in low..=high {
+            let stroke = if note.rem_euclid(12) == 0 { "#bbb" } else { "#eee" };
+            let _ = writeln!(svg, r#"<line x1="{x:.1}" y1="{}" x2="{x:.1}" y2="{}" stroke="{}"/>"#, MARGIN, CHART_HEIGHT - MARGIN, stroke, x = x(note));
+            if note.rem_euclid(12) == 0 || note == low || note == high {
+                let _ = writeln!(svg, r#"<text x="{:.1}" y="{}" text-anchor="middle">{}</text>"#, x(note), CHART_HEIGHT - MARGIN + 15.0, pitch::midi_name(note));
+            }
+        }
+
+        // Loudest levels from low to high, then softest levels back down, enclose the range
+        let outline: Vec<String> = notes.iter().map(|(note, levels)| format!("{:.1},{:.1}", x(*note), y(levels.loudest())))
+            .chain(notes.iter().rev().map(|(note, levels)| format!("{:.1},{:.1}", x(*note), y(levels.softest()))))
+            .collect();
+        if !outline.is_empty() {
+            let _ = writeln!(svg, r##"<polygon points="{}" fill="#4a90d9" fill-opacity="0.35" stroke="#1f5fa8" stroke-width="1.5"/>"##, outline.join(" "));
+        }
+        for (note, levels) in &notes {
+            let _ = writeln!(svg, r##"<line x1="{x:.1}" y1="{:.1}" x2="{x:.1}" y2="{:.1}" stroke="#1f5fa8"/>"##, y(levels.loudest()), y(levels.softest()), x = x(*note));
+        }
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">Pitch (semitones)</text>"#, CHART_WIDTH / 2.0, CHART_HEIGHT - 10.0);
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
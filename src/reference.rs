@@ -0,0 +1,57 @@
+/// Metric profiles of clips and their distance from a reference voice
+use color_eyre::eyre::Result;
+use crate::analysis::{LazyAnalysis, Metric};
+use crate::db::Db;
+use crate::goals;
+
+/// Metrics that make up a profile, with the difference that counts as one unit of distance.
+/// Frequencies are compared in semitones, the other metrics in their own units
+pub const PROFILE: [(Metric, f32); 7] = [
+    (Metric::Pitch, 2.0),
+    (Metric::F1, 1.0),
+    (Metric::F2, 1.0),
+    (Metric::F3, 1.0),
+    (Metric::Cpps, 2.0),
+    (Metric::H1H2, 3.0),
+    (Metric::Centroid, 2.0),
+];
+
+/// The medians of the profile metrics over a clip's voiced time
+#[derive(Debug, Clone)]
+pub struct Profile {
+    values: Vec<Option<f32>>,
+}
+
+impl Profile {
+    /// Profile of a clip, using and filling the database's cached medians
+    pub fn of(db: &Db, analysis: &mut LazyAnalysis) -> Result<Profile> {
+        let values = PROFILE.iter().map(|(metric, _)| goals::median(db, analysis, *metric)).collect::<Result<_>>()?;
+        Ok(Profile { values })
+    }
+
+    /// How far this profile lies from `target` per metric, in the metric's units (semitones for frequencies)
+    pub fn differences(&self, target: &Profile) -> Vec<(Metric, f32)> {
+        PROFILE.iter().zip(self.values.iter().zip(&target.values))
+            .filter_map(|((metric, _), (value, target))| {
+                let (value, target) = ((*value)?, (*target)?);
+                let difference = if metric.is_frequency() { 12.0 * (value / target).log2() } else { value - target };
+                Some((*metric, difference))
+            })
+            .collect()
+    }
+
+    /// Root mean square of the scaled differences from `target`, or None if no metric could be compared
+    pub fn distance(&self, target: &Profile) -> Option<f32> {
+        let differences = self.differences(target);
+        if differences.is_empty() {
+            return None;
+        }
+        let squares: f32 = differences.iter()
+            .map(|(metric, difference)| {
+                let scale = PROFILE.iter().find(|(profile_metric, _)| profile_metric == metric).map_or(1.0, |(_, scale)| *scale);
+                (difference / scale).powi(2)
+            })
+            .sum();
+        Some((squares / differences.len() as f32).sqrt())
+    }
+}
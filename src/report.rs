@@ -0,0 +1,199 @@
+/// Self-contained HTML progress reports with trend charts, session tables and audio snippets
+use std::fmt::Write;
+use base64::Engine;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use color_eyre::eyre::Result;
+use crate::analysis::{LazyAnalysis, Metric};
+use crate::audio_clips::AudioClip;
+use crate::db::Db;
+use crate::edits::Edit;
+use crate::goals::{self, Goal};
+use crate::stats::{self, PracticeStats};
+
+/// Metrics charted over the reported period
+const TREND_METRICS: [Metric; 3] = [Metric::Pitch, Metric::F2, Metric::Brightness];
+/// Longest audio snippet embedded per clip, in seconds
+pub const SNIPPET_SECONDS: f64 = 30.0;
+/// Trend chart size in pixels
+const CHART_WIDTH: f32 = 720.0;
+const CHART_HEIGHT: f32 = 240.0;
+/// Space around the plot area for labels, in pixels
+const MARGIN: f32 = 50.0;
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: 2em auto; color: #222 }
+table { border-collapse: collapse; margin-bottom: 1.5em }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.6em; text-align: right }
+th:first-child, td:first-child { text-align: left }
+.notes { background: #f5f7fa; border-left: 4px solid #4a90d9; padding: 0.5em 1em }";
+
+/// What goes into a report
+pub struct Report<'a> {
+    /// First and last day covered, in local time
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Journal clips recorded in the period
+    pub audio_clips: Vec<AudioClip>,
+    /// Metrics whose medians are listed per clip
+    pub metrics: &'a [Metric],
+    pub goals: Vec<Goal>,
+    /// Free text notes, one paragraph each
+    pub notes: Vec<String>,
+    /// Clips embedded as audio
+    pub snippets: Vec<AudioClip>,
+}
+
+impl Report<'_> {
+    /// The report as a single HTML page without external resources
+    pub fn to_html(&self, db: &Db) -> Result<String> {
+        let mut html = String::new();
+        let period = format!("{} to {}", self.from.format("%Y-%m-%d"), self.to.format("%Y-%m-%d"));
+        let _ = writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">");
+        let _ = writeln!(html, "<title>Voice practice report {}</title>\n<style>\n{}\n</style>\n</head>\n<body>", period, STYLE);
+        let _ = writeln!(html, "<h1>Voice practice report</h1>\n<p>{} (generated {})</p>", period, Local::now().format("%Y-%m-%d %H:%M"));
+
+        let stats = PracticeStats::of(&self.audio_clips)?;
+        let _ = writeln!(
+            html,
+            "<p>{} recorded in {} clip(s) over {} session(s) on {} day(s). Longest daily streak: {} day(s).</p>",
+            stats::format_time(stats.total_time), stats.clips, stats.sessions.len(), stats.days.len(), stats.longest_streak(),
+        );
+
+        if !self.notes.is_empty() {
+            html.push_str("<h2>Notes</h2>\n<div class=\"notes\">\n");
+            for note in &self.notes {
+                let _ = writeln!(html, "<p>{}</p>", escape(note));
+            }
+            html.push_str("</div>\n");
+        }
+
+        // Medians of every clip, oldest first, shared by the charts and the tables
+        let sessions = stats::sessions(&self.audio_clips)?;
+        let mut rows = Vec::new();
+        for audio_clip in sessions.iter().flatten() {
+            let mut analysis = LazyAnalysis::new(audio_clip);
+            let medians = self.metrics.iter().chain(&TREND_METRICS)
+                .map(|metric| Ok((*metric, goals::median(db, &mut analysis, *metric)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let scores = self.goals.iter().map(|goal| goals::score(db, &mut analysis, goal)).collect::<Result<Vec<_>>>()?;
+            rows.push((audio_clip.created_at, medians, scores));
+        }
+
+        html.push_str("<h2>Trends</h2>\n");
+        for metric in TREND_METRICS {
+            let points: Vec<(DateTime<Utc>, f32)> = rows.iter()
+                .filter_map(|(time, medians, _)| {
+                    let median = medians.iter().find(|(median_metric, _)| *median_metric == metric)?.1?;
+                    Some((*time, median))
+                })
+                .collect();
+            let band = self.goals.iter().find(|goal| goal.metric == metric).map(|goal| (goal.min, goal.max));
+            html.push_str(&trend_svg(metric, &points, band));
+        }
+
+        html.push_str("<h2>Sessions</h2>\n");
+        let mut rows = rows.iter();
+        for session in &sessions {
+            let start = session[0].created_at.with_timezone(&Local);
+            let time: f64 = session.iter().map(|audio_clip| audio_clip.duration()).sum::<Result<f64>>()?;
+            let _ = writeln!(html, "<h3>{} ({})</h3>\n<table>\n<tr><th>Clip</th><th>Time</th><th>Exercise</th><th>Length</th>", start.format("%A %Y-%m-%d %H:%M"), stats::format_time(time));
+            for metric in self.metrics {
+                let _ = write!(html, "<th>{} ({})</th>", metric, metric.unit());
+            }
+            for goal in &self.goals {
+                let _ = write!(html, "<th>{}</th>", escape(&goal.name));
+            }
+            html.push_str("</tr>\n");
+            for (audio_clip, (_, medians, scores)) in session.iter().zip(rows.by_ref()) {
+                let _ = write!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+                    escape(&audio_clip.name),
+                    audio_clip.created_at.with_timezone(&Local).format("%H:%M"),
+                    escape(audio_clip.exercise.as_deref().unwrap_or("-")),
+                    stats::format_time(audio_clip.duration()?),
+                );
+                for (_, median) in &medians[..self.metrics.len()] {
+                    let _ = write!(html, "<td>{}</td>", median.map_or("-".to_string(), |median| format!("{:.1}", median)));
+                }
+                for score in scores {
+                    let _ = write!(html, "<td>{}</td>", score.map_or("-".to_string(), |score| format!("{:.0}%", score * 100.0)));
+                }
+                html.push_str("</tr>\n");
+            }
+            html.push_str("</table>\n");
+        }
+        if sessions.is_empty() {
+            html.push_str("<p>No clips were recorded in this period.</p>\n");
+        }
+
+        if !self.snippets.is_empty() {
+            html.push_str("<h2>Recordings</h2>\n");
+            for audio_clip in &self.snippets {
+                let mut snippet = audio_clip.clone();
+                let duration = snippet.duration()?;
+                if duration > SNIPPET_SECONDS {
+                    snippet.add_edit(Edit::Crop { start: 0.0, end: SNIPPET_SECONDS })?;
+                }
+                let ogg = base64::engine::general_purpose::STANDARD.encode(snippet.encode_ogg()?);
+                let _ = writeln!(
+                    html,
+                    "<p>{} ({}, {})<br>\n<audio controls src=\"data:audio/ogg;base64,{}\"></audio></p>",
+                    escape(&audio_clip.name), audio_clip.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"), stats::format_time(snippet.duration()?), ogg,
+                );
+            }
+        }
+
+        html.push_str("</body>\n</html>\n");
+        Ok(html)
+    }
+}
+
+// Line chart of a metric's clip medians over time, with the goal range shaded if there is one
+fn trend_svg(metric: Metric, points: &[(DateTime<Utc>, f32)], band: Option<(f32, f32)>) -> String {
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="11">"#, CHART_WIDTH, CHART_HEIGHT);
+    let _ = writeln!(svg, r#"<text x="{}" y="20" font-size="14" text-anchor="middle">Median {} ({})</text>"#, CHART_WIDTH / 2.0, metric, metric.unit());
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        let _ = writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">No measurements</text>"#, CHART_WIDTH / 2.0, CHART_HEIGHT / 2.0);
+        svg.push_str("</svg>\n");
+        return svg;
+    };
+
+    let values = points.iter().map(|(_, value)| *value).chain(band.into_iter().flat_map(|(min, max)| [min, max]));
+    let (low, high) = values.fold((f32::MAX, f32::MIN), |(low, high), value| (low.min(value), high.max(value)));
+    let padding = ((high - low) * 0.1).max(1.0);
+    let (low, high) = (low - padding, high + padding);
+    let span = (last.0 - first.0).num_seconds().max(1) as f32;
+    let x = |time: DateTime<Utc>| {
+        if points.len() == 1 { CHART_WIDTH / 2.0 } else { MARGIN + (time - first.0).num_seconds() as f32 / span * (CHART_WIDTH - 2.0 * MARGIN) }
+    };
+    let y = |value: f32| CHART_HEIGHT - MARGIN - (value - low) / (high - low) * (CHART_HEIGHT - 2.0 * MARGIN);
+
+    if let Some((min, max)) = band {
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{}" y="{:.1}" width="{}" height="{:.1}" fill="#7bc47f" fill-opacity="0.25"/>"##,
+            MARGIN, y(max), CHART_WIDTH - 2.0 * MARGIN, y(min) - y(max),
+        );
+    }
+    for value in [low + padding, (low + high) / 2.0, high - padding] {
+        let _ = writeln!(svg, r##"<line x1="{}" y1="{y:.1}" x2="{}" y2="{y:.1}" stroke="#ddd"/>"##, MARGIN, CHART_WIDTH - MARGIN, y = y(value));
+        let _ = writeln!(svg, r#"<text x="{}" y="{:.1}" text-anchor="end">{:.0}</text>"#, MARGIN - 5.0, y(value) + 4.0, value);
+    }
+    for (time, anchor) in [(first.0, "start"), (last.0, "end")] {
+        let label = time.with_timezone(&Local).format("%Y-%m-%d");
+        let _ = writeln!(svg, r#"<text x="{:.1}" y="{}" text-anchor="{}">{}</text>"#, x(time), CHART_HEIGHT - MARGIN + 15.0, anchor, label);
+    }
+    let line: Vec<String> = points.iter().map(|(time, value)| format!("{:.1},{:.1}", x(*time), y(*value))).collect();
+    let _ = writeln!(svg, r##"<polyline points="{}" fill="none" stroke="#1f5fa8" stroke-width="1.5"/>"##, line.join(" "));
+    for (time, value) in points {
+        let _ = writeln!(svg, r##"<circle cx="{:.1}" cy="{:.1}" r="3" fill="#1f5fa8"/>"##, x(*time), y(*value));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Escape text for use in HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,32 @@
+/// Resonance measures: vocal tract length from formant spacing and an overall brightness score
+use crate::analysis::Metric;
+
+/// Metrics written by `oxygen resonance --tracks`
+pub const TRACK_METRICS: [Metric; 6] = [Metric::Centroid, Metric::F1, Metric::F2, Metric::F3, Metric::Vtl, Metric::Brightness];
+/// Speed of sound in warm, humid air in cm/s
+const SPEED_OF_SOUND: f32 = 35000.0;
+/// Dark and bright anchors of each brightness component, roughly typical adult male and female speech
+const VTL_RANGE: (f32, f32) = (17.5, 14.0);
+const CENTROID_RANGE: (f32, f32) = (600.0, 1500.0);
+const F2_RANGE: (f32, f32) = (1200.0, 1900.0);
+
+/// Vocal tract length in cm of a uniform tube closed at the glottis with the given formants
+pub fn vocal_tract_length(formants: [f32; 3]) -> f32 {
+    // Formant n of such a tube lies at (2n - 1) / 2 times the spacing c / 2L, so the spacing is
+    // the least squares slope through the origin of the formants against those positions
+    let positions = [0.5, 1.5, 2.5];
+    let spacing = formants.iter().zip(positions).map(|(formant, position)| formant * position).sum::<f32>()
+        / positions.iter().map(|position| position * position).sum::<f32>();
+    SPEED_OF_SOUND / (2.0 * spacing)
+}
+
+/// Brightness from 0 (dark) to 100 (bright), combining vocal tract length, spectral centroid and F2
+pub fn brightness(centroid: f32, formants: [f32; 3]) -> f32 {
+    let scale = |value: f32, (dark, bright): (f32, f32)| ((value - dark) / (bright - dark)).clamp(0.0, 1.0);
+    let components = [
+        scale(vocal_tract_length(formants), VTL_RANGE),
+        scale(centroid, CENTROID_RANGE),
+        scale(formants[1], F2_RANGE),
+    ];
+    components.iter().sum::<f32>() / components.len() as f32 * 100.0
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Pitch and formant shifting with time-domain pitch-synchronous overlap-add (TD-PSOLA)
+use color_eyre::eyre::{Report, Result, eyre};
+use crate::dsp;
+use crate::pitch::PitchTracker;
+
+/// Spacing of the analysis marks in unvoiced audio, in seconds
+const UNVOICED_PERIOD: f64 = 0.01;
+/// Largest shift accepted in either direction, in semitones
+pub const MAX_SEMITONES: f32 = 12.0;
+/// Formant ratios accepted, roughly the span between typical adult vocal tract lengths
+pub const FORMANT_RATIO_RANGE: (f64, f64) = (0.7, 1.4);
+
+/// A pitch interval such as `+3st` or `-1.5st`. The unit is optional
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Semitones(pub f32);
+
+impl Semitones {
+    /// The frequency ratio of the interval
+    pub fn ratio(&self) -> f64 {
+        2f64.powf(self.0 as f64 / 12.0)
+    }
+}
+
+impl fmt::Display for Semitones {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:+}st", self.0)
+    }
+}
+
+impl FromStr for Semitones {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Semitones> {
+        let number = s.trim().strip_suffix("st").unwrap_or(s.trim());
+        let semitones: f32 = number.parse().map_err(|_| eyre!("Invalid pitch shift '{}', expected e.g. +3st", s))?;
+        if !semitones.is_finite() || semitones.abs() > MAX_SEMITONES {
+            return Err(eyre!("Pitch shifts are limited to {} semitones either way", MAX_SEMITONES));
+        }
+        Ok(Semitones(semitones))
+    }
+}
+
+/// Multiply the pitch of `samples` by `pitch_ratio` and the formant frequencies by `formant_ratio`, keeping the duration.
+/// The ratios are independent, so a formant ratio alone warps the spectral envelope without changing the pitch
+pub fn shift(samples: &[f32], sample_rate: u32, pitch_ratio: f64, formant_ratio: f64) -> Vec<f32> {
+    if formant_ratio == 1.0 {
+        return psola(samples, sample_rate, pitch_ratio, 1.0);
+    }
+    // Resampling moves pitch and formants together and changes the duration; PSOLA then corrects pitch and duration
+    // while keeping the resampled spectral envelope
+    let resampled = dsp::resample_by(samples, formant_ratio);
+    let mut shifted = psola(&resampled, sample_rate, pitch_ratio / formant_ratio, formant_ratio);
+    shifted.resize(samples.len(), 0.0);
+    shifted
+}
+
+// Rebuild the signal from two-period grains around each glottal pulse, spaced for the new pitch and stretched in time
+// by `time_ratio`. Unvoiced audio is copied with fixed spacing so noise keeps its character
+fn psola(samples: &[f32], sample_rate: u32, pitch_ratio: f64, time_ratio: f64) -> Vec<f32> {
+    let marks = analysis_marks(samples, sample_rate);
+    let len = (samples.len() as f64 * time_ratio) as usize;
+    let mut output = vec![0.0f32; len];
+    let mut windows: HashMap<usize, Vec<f32>> = HashMap::new();
+
+    let mut k = 0;
+    let mut time = marks.first().map_or(0.0, |mark| mark.position * time_ratio);
+    while time < len as f64 {
+        // Nearest analysis mark to the matching input time
+        let source = time / time_ratio;
+        while k + 1 < marks.len() && (marks[k + 1].position - source).abs() <= (marks[k].position - source).abs() {
+            k += 1;
+        }
+        let mark = marks[k];
+        let step = if mark.voiced { mark.period as f64 / pitch_ratio } else { mark.period as f64 };
+        // Grains overlap more (or less) when their spacing changes; scale them so the level stays the same
+        let gain = (step / mark.period as f64) as f32;
+
+        let window = windows.entry(mark.period).or_insert_with(|| dsp::hann(2 * mark.period));
+        let from = mark.position.round() as isize - mark.period as isize;
+        let to = time.round() as isize - mark.period as isize;
+        for (j, weight) in window.iter().enumerate() {
+            let (Ok(from), Ok(to)) = (usize::try_from(from + j as isize), usize::try_from(to + j as isize)) else { continue };
+            if let (Some(sample), Some(out)) = (samples.get(from), output.get_mut(to)) {
+                *out += sample * weight * gain;
+            }
+        }
+        time += step;
+    }
+    output
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    /// Position in samples
+    position: f64,
+    /// Local period in samples
+    period: usize,
+    voiced: bool,
+}
+
+// Glottal pulses in voiced stretches, with evenly spaced marks filling the unvoiced audio around them
+fn analysis_marks(samples: &[f32], sample_rate: u32) -> Vec<Mark> {
+    let unvoiced = (UNVOICED_PERIOD * sample_rate as f64) as usize;
+    let mut marks = Vec::new();
+    let mut position = 0.0;
+    let fill = |marks: &mut Vec<Mark>, position: &mut f64, until: f64| {
+        while *position < until - unvoiced as f64 / 2.0 {
+            marks.push(Mark { position: *position, period: unvoiced, voiced: false });
+            *position += unvoiced as f64;
+        }
+    };
+
+    for stretch in PitchTracker::new(sample_rate).period_marks(samples) {
+        if stretch.len() < 2 {
+            continue;
+        }
+        fill(&mut marks, &mut position, stretch[0]);
+        for i in 0..stretch.len() {
+            let before = stretch[i.saturating_sub(1)];
+            let after = stretch[(i + 1).min(stretch.len() - 1)];
+            let spacing = (after - before) / if i == 0 || i == stretch.len() - 1 { 1.0 } else { 2.0 };
+            marks.push(Mark { position: stretch[i], period: (spacing.round() as usize).max(1), voiced: true });
+        }
+        position = stretch[stretch.len() - 1] + (stretch[stretch.len() - 1] - stretch[stretch.len() - 2]);
+    }
+    fill(&mut marks, &mut position, samples.len() as f64 + unvoiced as f64);
+    marks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formants::FormantTracker;
+    use crate::test_signals;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn median(mut values: Vec<f32>) -> f32 {
+        assert!(!values.is_empty());
+        values.sort_by(f32::total_cmp);
+        values[values.len() / 2]
+    }
+
+    // Median F0, F1 and F2 over the middle of the clip, away from the edges
+    fn measure(samples: &[f32]) -> (f32, f32, f32) {
+        let duration = samples.len() as f64 / SAMPLE_RATE as f64;
+        let f0 = PitchTracker::new(SAMPLE_RATE).track(samples, 0.01).into_iter()
+            .filter(|frame| frame.time > 0.2 && frame.time < duration - 0.2)
+            .filter_map(|frame| frame.f0)
+            .collect();
+        let tracker = FormantTracker::new(samples, SAMPLE_RATE);
+        let formants: Vec<[f32; 3]> = (20..(duration * 100.0) as usize - 20)
+            .filter_map(|i| tracker.estimate(i as f64 / 100.0))
+            .collect();
+        (median(f0), median(formants.iter().map(|f| f[0]).collect()), median(formants.iter().map(|f| f[1]).collect()))
+    }
+
+    #[test]
+    fn formant_shift_keeps_pitch() {
+        let samples = test_signals::vowel(150.0, &[700.0, 1200.0, 2600.0], 1.5, SAMPLE_RATE);
+        let (f0, f1, f2) = measure(&samples);
+        for ratio in [0.85, 1.15] {
+            let (shifted_f0, shifted_f1, shifted_f2) = measure(&shift(&samples, SAMPLE_RATE, 1.0, ratio));
+            assert!((shifted_f0 / f0 - 1.0).abs() < 0.02, "ratio {ratio}: F0 {f0} -> {shifted_f0}");
+            for (before, after) in [(f1, shifted_f1), (f2, shifted_f2)] {
+                let moved = (after / before) as f64;
+                assert!((moved / ratio - 1.0).abs() < 0.07, "ratio {ratio}: formant {before} -> {after}");
+            }
+        }
+    }
+
+    #[test]
+    fn pitch_shift_moves_f0_by_the_interval() {
+        let samples = test_signals::vowel(150.0, &[700.0, 1200.0, 2600.0], 1.5, SAMPLE_RATE);
+        let (f0, f1, f2) = measure(&samples);
+        for semitones in [Semitones(-3.0), Semitones(3.0)] {
+            let ratio = semitones.ratio();
+            for keep_formants in [false, true] {
+                let formant_ratio = if keep_formants { 1.0 } else { ratio };
+                let shifted = shift(&samples, SAMPLE_RATE, ratio, formant_ratio);
+                assert_eq!(shifted.len(), samples.len());
+                let (shifted_f0, shifted_f1, shifted_f2) = measure(&shifted);
+                let context = format!("{} keeping formants {}", semitones, keep_formants);
+                assert!(((shifted_f0 / f0) as f64 / ratio - 1.0).abs() < 0.02, "{}: F0 {} -> {}", context, f0, shifted_f0);
+                for (before, after) in [(f1, shifted_f1), (f2, shifted_f2)] {
+                    assert!(((after / before) as f64 / formant_ratio - 1.0).abs() < 0.05, "{}: formant {} -> {}", context, before, after);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+/// Practice time, sessions and streaks computed from the journal clips
+use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, Days, Duration, Local, NaiveDate, Utc};
+use color_eyre::eyre::Result;
+use crate::audio_clips::AudioClip;
+
+/// Clips recorded less than this many minutes after the previous one ended belong to the same session
+const SESSION_GAP_MINUTES: i64 = 30;
+
+/// Name under which time spent on clips without an exercise is listed
+pub const FREE_PRACTICE: &str = "(free practice)";
+
+/// Summary of the practice recorded in the journal, with days in local time
+#[derive(Debug, Clone, Default)]
+pub struct PracticeStats {
+    pub clips: usize,
+    /// Total recorded time in seconds
+    pub total_time: f64,
+    /// Start of each session, oldest first
+    pub sessions: Vec<DateTime<Local>>,
+    /// Recorded seconds per day with practice
+    pub days: BTreeMap<NaiveDate, f64>,
+    /// Recorded seconds per exercise
+    pub exercises: BTreeMap<String, f64>,
+}
+
+impl PracticeStats {
+    /// Statistics of the given clips, skipping reference clips
+    pub fn of(audio_clips: &[AudioClip]) -> Result<PracticeStats> {
+        let mut stats = PracticeStats::default();
+        for session in sessions(audio_clips)? {
+            stats.sessions.push(session[0].created_at.with_timezone(&Local));
+            for audio_clip in session {
+                let duration = audio_clip.duration()?;
+                stats.clips += 1;
+                stats.total_time += duration;
+                *stats.days.entry(audio_clip.created_at.with_timezone(&Local).date_naive()).or_default() += duration;
+                let exercise = audio_clip.exercise.clone().unwrap_or_else(|| FREE_PRACTICE.to_string());
+                *stats.exercises.entry(exercise).or_default() += duration;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Recorded seconds on a day
+    pub fn time_on(&self, day: NaiveDate) -> f64 {
+        self.days.get(&day).copied().unwrap_or(0.0)
+    }
+
+    /// Consecutive days with practice up to `today`. A streak that reached yesterday still counts until today is over
+    pub fn current_streak(&self, today: NaiveDate) -> usize {
+        let mut day = if self.days.contains_key(&today) { today } else { today - Days::new(1) };
+        let mut streak = 0;
+        while self.days.contains_key(&day) {
+            streak += 1;
+            day = day - Days::new(1);
+        }
+        streak
+    }
+
+    /// Most consecutive days with practice
+    pub fn longest_streak(&self) -> usize {
+        let mut longest = 0;
+        let mut streak = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for &day in self.days.keys() {
+            streak = if previous.is_some_and(|previous| previous + Days::new(1) == day) { streak + 1 } else { 1 };
+            longest = longest.max(streak);
+            previous = Some(day);
+        }
+        longest
+    }
+
+    /// Number of sessions in each of the last `weeks` weeks (starting on Monday) up to the week of `today`, oldest first
+    pub fn sessions_per_week(&self, weeks: usize, today: NaiveDate) -> Vec<(NaiveDate, usize)> {
+        let this_week = week_start(today);
+        (0..weeks as u64).rev()
+            .map(|ago| {
+                let week = this_week - Days::new(7 * ago);
+                let sessions = self.sessions.iter().filter(|start| week_start(start.date_naive()) == week).count();
+                (week, sessions)
+            })
+            .collect()
+    }
+}
+
+/// The journal clips grouped into practice sessions, oldest first. Reference clips are skipped
+pub fn sessions(audio_clips: &[AudioClip]) -> Result<Vec<Vec<&AudioClip>>> {
+    let mut audio_clips: Vec<&AudioClip> = audio_clips.iter().filter(|audio_clip| !audio_clip.reference).collect();
+    audio_clips.sort_by_key(|audio_clip| audio_clip.created_at);
+
+    let mut sessions: Vec<Vec<&AudioClip>> = Vec::new();
+    let mut session_end: Option<DateTime<Utc>> = None;
+    for audio_clip in audio_clips {
+        let start = audio_clip.created_at;
+        let end = start + Duration::milliseconds((audio_clip.duration()? * 1000.0) as i64);
+        match sessions.last_mut() {
+            Some(session) if session_end.is_some_and(|session_end| start - session_end <= Duration::minutes(SESSION_GAP_MINUTES)) => {
+                session.push(audio_clip);
+            }
+            _ => sessions.push(vec![audio_clip]),
+        }
+        session_end = Some(session_end.map_or(end, |session_end| session_end.max(end)));
+    }
+    Ok(sessions)
+}
+
+// Monday of the week a day falls in
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Days::new(day.weekday().num_days_from_monday() as u64)
+}
+
+/// A duration in seconds as hours, minutes and seconds
+pub fn format_time(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals;
+
+    // A one minute clip recorded `minutes` after a fixed start
+    fn clip_at(minutes: i64) -> AudioClip {
+        let mut audio_clip = test_signals::clip(vec![0.0; 60 * 100], 100);
+        audio_clip.created_at = "2026-03-02T09:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes);
+        audio_clip
+    }
+
+    fn stats_on(days: &[&str]) -> PracticeStats {
+        PracticeStats { days: days.iter().map(|day| (day.parse().unwrap(), 60.0)).collect(), ..PracticeStats::default() }
+    }
+
+    fn date(day: &str) -> NaiveDate {
+        day.parse().unwrap()
+    }
+
+    #[test]
+    fn sessions_split_after_a_30_minute_gap() {
+        // The second clip starts exactly 30 minutes after the first ends, the third 31 minutes after the second
+        let mut reference = clip_at(40);
+        reference.reference = true;
+        let audio_clips = [clip_at(31), clip_at(0), reference, clip_at(63), clip_at(64)];
+        let sessions = sessions(&audio_clips).unwrap();
+        let starts: Vec<Vec<i64>> = sessions.iter()
+            .map(|session| session.iter().map(|audio_clip| (audio_clip.created_at - clip_at(0).created_at).num_minutes()).collect())
+            .collect();
+        assert_eq!(starts, [vec![0, 31], vec![63, 64]]);
+    }
+
+    #[test]
+    fn streaks_count_consecutive_days() {
+        let stats = stats_on(&["2026-03-01", "2026-03-02", "2026-03-03", "2026-03-05", "2026-03-06"]);
+        assert_eq!(stats.longest_streak(), 3);
+        assert_eq!(stats.current_streak(date("2026-03-06")), 2);
+        // Yesterday's streak still counts while today has no practice yet
+        assert_eq!(stats.current_streak(date("2026-03-07")), 2);
+        assert_eq!(stats.current_streak(date("2026-03-08")), 0);
+        assert_eq!(stats.current_streak(date("2026-03-04")), 3);
+        assert_eq!(PracticeStats::default().longest_streak(), 0);
+    }
+}
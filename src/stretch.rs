@@ -0,0 +1,120 @@
+/// Time stretching without pitch change by waveform similarity overlap-add (WSOLA)
+use crate::dsp;
+
+/// Slowest and fastest playback speeds
+pub const SPEED_RANGE: (f64, f64) = (0.5, 2.0);
+/// Length of the overlapping frames in seconds
+const FRAME: f64 = 0.04;
+/// How far a frame may move from its nominal position to line up with the previous one, in seconds
+const TOLERANCE: f64 = 0.01;
+/// Only every this many lags and samples are compared when lining frames up, which is plenty for speech
+const SEARCH_STEP: usize = 4;
+
+/// Streaming WSOLA stretcher producing output one hop at a time, so the speed can change between hops
+pub struct Wsola {
+    hop: usize,
+    tolerance: usize,
+    window: Vec<f32>,
+    /// Nominal input position of the next frame in samples
+    position: f64,
+    /// Input position that would continue the previous frame seamlessly
+    natural: usize,
+    /// Windowed second half of the previous frame, waiting to be overlapped
+    tail: Vec<f32>,
+}
+
+impl Wsola {
+    pub fn new(sample_rate: u32) -> Wsola {
+        let hop = (FRAME * sample_rate as f64 / 2.0) as usize;
+        Wsola {
+            hop,
+            tolerance: (TOLERANCE * sample_rate as f64) as usize,
+            window: dsp::hann(2 * hop),
+            position: 0.0,
+            natural: 0,
+            tail: vec![0.0; hop],
+        }
+    }
+
+    /// Input position of the next frame in samples
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    /// The next hop of output played at `speed` (2.0 is twice as fast), or None once `samples` are used up
+    pub fn next_hop(&mut self, samples: &[f32], speed: f64) -> Option<Vec<f32>> {
+        if self.position >= samples.len() as f64 {
+            return None;
+        }
+        let nominal = self.position as usize;
+        let start = self.best_start(samples, nominal);
+        let frame: Vec<f32> = (0..2 * self.hop)
+            .map(|i| samples.get(start + i).copied().unwrap_or(0.0) * self.window[i])
+            .collect();
+        let output = self.tail.iter().zip(&frame[..self.hop]).map(|(tail, head)| tail + head).collect();
+        self.tail = frame[self.hop..].to_vec();
+        self.natural = start + self.hop;
+        self.position += self.hop as f64 * speed;
+        Some(output)
+    }
+
+    // Frame start within the tolerance around `nominal` whose first half best matches the natural continuation
+    fn best_start(&self, samples: &[f32], nominal: usize) -> usize {
+        if nominal == self.natural {
+            return nominal;
+        }
+        let Some(target) = samples.get(self.natural..self.natural + self.hop) else { return nominal };
+        let low = nominal.saturating_sub(self.tolerance);
+        let high = (nominal + self.tolerance).min(samples.len().saturating_sub(self.hop));
+        (low..=high.max(low)).step_by(SEARCH_STEP)
+            .filter_map(|start| {
+                let candidate = samples.get(start..start + self.hop)?;
+                let correlation: f32 = target.iter().zip(candidate).step_by(SEARCH_STEP).map(|(a, b)| a * b).sum();
+                Some((start, correlation))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(nominal, |(start, _)| start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::PitchTracker;
+    use crate::test_signals;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn stretch(samples: &[f32], speed: f64) -> Vec<f32> {
+        let mut wsola = Wsola::new(SAMPLE_RATE);
+        let mut output = Vec::new();
+        while let Some(hop) = wsola.next_hop(samples, speed) {
+            output.extend(hop);
+        }
+        output
+    }
+
+    fn median_f0(samples: &[f32]) -> f32 {
+        let duration = samples.len() as f64 / SAMPLE_RATE as f64;
+        let mut f0: Vec<f32> = PitchTracker::new(SAMPLE_RATE).track(samples, 0.01).into_iter()
+            .filter(|frame| frame.time > 0.1 && frame.time < duration - 0.1)
+            .filter_map(|frame| frame.f0)
+            .collect();
+        assert!(!f0.is_empty());
+        f0.sort_by(f32::total_cmp);
+        f0[f0.len() / 2]
+    }
+
+    #[test]
+    fn speed_changes_duration_but_not_pitch() {
+        let samples = test_signals::harmonic(150.0, 2.0, SAMPLE_RATE);
+        let hop = (FRAME * SAMPLE_RATE as f64 / 2.0) as usize;
+        for speed in [0.5, 0.8, 1.0, 1.5, 2.0] {
+            let output = stretch(&samples, speed);
+            let expected = samples.len() as f64 / speed;
+            assert!((output.len() as f64 - expected).abs() <= hop as f64, "speed {}: {} samples, expected {}", speed, output.len(), expected);
+            let f0 = median_f0(&output);
+            assert!((f0 / 150.0 - 1.0).abs() < 0.02, "speed {}: F0 {}", speed, f0);
+        }
+    }
+}
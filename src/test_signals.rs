@@ -0,0 +1,103 @@
+/// Synthetic signals with known properties for the unit tests
+use std::f64::consts::PI;
+use chrono::Utc;
+use crate::audio_clips::AudioClip;
+use crate::edits::EditList;
+
+// Small deterministic generator so the tests do not depend on a random crate
+struct Lcg(u64);
+
+impl Lcg {
+    fn uniform(&mut self) -> f64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+
+    fn gaussian(&mut self) -> f64 {
+        (-2.0 * self.uniform().ln()).sqrt() * (2.0 * PI * self.uniform()).cos()
+    }
+}
+
+/// Gaussian white noise with standard deviation `level`
+pub fn noise(duration: f64, level: f64, sample_rate: u32, seed: u64) -> Vec<f32> {
+    let mut lcg = Lcg(seed);
+    (0..(duration * sample_rate as f64) as usize).map(|_| (lcg.gaussian() * level) as f32).collect()
+}
+
+/// A steady voice-like tone: the first ten harmonics of `f0` with falling amplitudes, peaking around 0.3
+pub fn harmonic(f0: f64, duration: f64, sample_rate: u32) -> Vec<f32> {
+    (0..(duration * sample_rate as f64) as usize)
+        .map(|i| {
+            let time = i as f64 / sample_rate as f64;
+            let sum: f64 = (1..=10).map(|k| (2.0 * PI * k as f64 * f0 * time).sin() / k as f64).sum();
+            (0.15 * sum) as f32
+        })
+        .collect()
+}
+
+/// An unsaved clip holding `samples`
+pub fn clip(samples: Vec<f32>, sample_rate: u32) -> AudioClip {
+    AudioClip {
+        id: None,
+        name: "test".to_string(),
+        created_at: Utc::now(),
+        samples,
+        sample_rate,
+        playback_position: 0,
+        edits: EditList::default(),
+        reference: false,
+        exercise: None,
+    }
+}
+
+/// A voice-like wave with one cycle per entry of `periods` (in seconds), each scaled by the matching amplitude.
+/// Cycles start at a zero crossing, so changing period or amplitude between them leaves no clicks
+pub fn cycles(periods: &[f64], amplitudes: &[f64], sample_rate: u32) -> Vec<f32> {
+    let mut samples = Vec::new();
+    let mut time = 0.0;
+    let mut start = 0.0;
+    for (period, amplitude) in periods.iter().zip(amplitudes) {
+        while time < start + period {
+            let phase = 2.0 * PI * (time - start) / period;
+            samples.push((amplitude * (phase.sin() + 0.3 * (2.0 * phase).sin())) as f32);
+            time += 1.0 / sample_rate as f64;
+        }
+        start += period;
+    }
+    samples
+}
+
+
+/// A sustained vowel at `f0` with resonances at `formants` (Hz), peaking around 0.3
+pub fn vowel(f0: f64, formants: &[f64], duration: f64, sample_rate: u32) -> Vec<f32> {
+    let rate = sample_rate as f64;
+    let len = (duration * rate) as usize;
+    // Impulse train through a two-pole glottal low pass
+    let period = rate / f0;
+    let mut source = vec![0.0f64; len];
+    let mut position = 0.0;
+    while (position as usize) < len {
+        source[position as usize] = 1.0;
+        position += period;
+    }
+    let (mut y, mut z) = (0.0, 0.0);
+    for sample in source.iter_mut() {
+        y = 0.97 * y + *sample;
+        z = 0.97 * z + y;
+        *sample = z;
+    }
+    // Each formant is a two-pole resonator with an 80 Hz bandwidth
+    for formant in formants {
+        let radius = (-PI * 80.0 / rate).exp();
+        let (a1, a2) = (2.0 * radius * (2.0 * PI * formant / rate).cos(), -radius * radius);
+        let (mut y1, mut y2) = (0.0, 0.0);
+        for sample in source.iter_mut() {
+            let y = *sample + a1 * y1 + a2 * y2;
+            y2 = y1;
+            y1 = y;
+            *sample = y;
+        }
+    }
+    let peak = source.iter().fold(0.0f64, |peak, sample| peak.max(sample.abs())).max(1e-12);
+    source.into_iter().map(|sample| (0.3 * sample / peak) as f32).collect()
+}
@@ -0,0 +1,192 @@
+/// Export of per-frame analysis tracks for spreadsheets and Praat
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use serde_json::{Map, Value, json};
+use crate::analysis::{Analysis, HOP, Metric};
+use crate::audio_clips::{AudioClip, Speech};
+
+/// Metrics written by the track exports
+pub const TRACK_METRICS: [Metric; 5] = [Metric::Pitch, Metric::F1, Metric::F2, Metric::F3, Metric::Level];
+
+/// File formats for exported tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TrackFormat {
+    /// One row per voiced frame with a column per metric
+    Csv,
+    /// The clip's details and an array of voiced frames
+    Json,
+    /// Praat PitchTier with the pitch of every voiced frame
+    PitchTier,
+    /// Praat TextGrid with an interval tier marking the speech segments
+    TextGrid,
+}
+
+impl TrackFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TrackFormat::Csv => "csv",
+            TrackFormat::Json => "json",
+            TrackFormat::PitchTier => "PitchTier",
+            TrackFormat::TextGrid => "TextGrid",
+        }
+    }
+}
+
+/// Write the tracks of an analysed clip. Times count from the start of the edited clip, or from the start of
+/// the original recording if `recorded` is set
+pub fn export(audio_clip: &AudioClip, speech: &Speech, analysis: &Analysis, format: TrackFormat, recorded: bool, path: &Path) -> Result<()> {
+    let (offset, end) = if recorded {
+        (audio_clip.edit_offset()?, audio_clip.samples.len() as f64 / audio_clip.sample_rate as f64)
+    } else {
+        (0.0, audio_clip.duration()?)
+    };
+    let mut file = BufWriter::new(File::create(path)?);
+    match format {
+        TrackFormat::Csv => write_csv(analysis, &TRACK_METRICS, offset, &mut file)?,
+        TrackFormat::Json => {
+            let frames: Vec<Value> = analysis.frames.iter()
+                .map(|frame| {
+                    let mut values = Map::new();
+                    values.insert("time".to_string(), json!(frame.time + offset));
+                    for metric in TRACK_METRICS {
+                        values.insert(metric.name().to_string(), json!(metric.value(frame)));
+                    }
+                    Value::Object(values)
+                })
+                .collect();
+            let units: Map<String, Value> = TRACK_METRICS.iter().map(|metric| (metric.name().to_string(), json!(metric.unit()))).collect();
+            let tracks = json!({
+                "clip": audio_clip.name,
+                "sample_rate": audio_clip.sample_rate,
+                "hop": HOP,
+                "start": offset,
+                "end": offset + audio_clip.duration()?,
+                "units": units,
+                "frames": frames,
+            });
+            serde_json::to_writer_pretty(&mut file, &tracks)?;
+            writeln!(file)?;
+        }
+        TrackFormat::PitchTier => {
+            write_praat_header(&mut file, "PitchTier", end)?;
+            writeln!(file, "points: size = {} ", analysis.frames.len())?;
+            for (index, frame) in analysis.frames.iter().enumerate() {
+                writeln!(file, "points [{}]:", index + 1)?;
+                writeln!(file, "    number = {} ", frame.time + offset)?;
+                writeln!(file, "    value = {} ", frame.f0)?;
+            }
+        }
+        TrackFormat::TextGrid => {
+            let sample_rate = audio_clip.sample_rate as f64;
+            // Intervals have to cover the whole tier, so the gaps between speech segments are empty intervals
+            let mut intervals = Vec::new();
+            let mut time = 0.0;
+            for segment in &speech.segments {
+                let (start, stop) = (offset + segment.start as f64 / sample_rate, offset + segment.end as f64 / sample_rate);
+                // Praat rejects overlapping intervals, so a segment never starts before the previous one ended
+                let start = start.max(time);
+                if stop <= start {
+                    continue;
+                }
+                if start > time {
+                    intervals.push((time, start, ""));
+                }
+                intervals.push((start, stop, "speech"));
+                time = stop;
+            }
+            if end > time {
+                intervals.push((time, end, ""));
+            }
+
+            write_praat_header(&mut file, "TextGrid", end)?;
+            writeln!(file, "tiers? <exists> ")?;
+            writeln!(file, "size = 1 ")?;
+            writeln!(file, "item []: ")?;
+            writeln!(file, "    item [1]:")?;
+            writeln!(file, "        class = \"IntervalTier\" ")?;
+            writeln!(file, "        name = \"speech\" ")?;
+            writeln!(file, "        xmin = 0 ")?;
+            writeln!(file, "        xmax = {} ", end)?;
+            writeln!(file, "        intervals: size = {} ", intervals.len())?;
+            for (index, (start, stop, text)) in intervals.iter().enumerate() {
+                writeln!(file, "        intervals [{}]:", index + 1)?;
+                writeln!(file, "            xmin = {} ", start)?;
+                writeln!(file, "            xmax = {} ", stop)?;
+                writeln!(file, "            text = \"{}\" ", text)?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/// Write the time and the given metrics of every voiced frame as CSV, leaving unmeasured values empty
+pub fn write_csv(analysis: &Analysis, metrics: &[Metric], offset: f64, writer: &mut impl Write) -> Result<()> {
+    let header: Vec<&str> = metrics.iter().map(|metric| metric.name()).collect();
+    writeln!(writer, "time,{}", header.join(","))?;
+    for frame in &analysis.frames {
+        let values: Vec<String> = metrics.iter()
+            .map(|metric| metric.value(frame).map_or(String::new(), |value| format!("{:.2}", value)))
+            .collect();
+        writeln!(writer, "{:.3},{}", frame.time + offset, values.join(","))?;
+    }
+    Ok(())
+}
+
+// Header of a Praat text file spanning `end` seconds from zero
+fn write_praat_header(writer: &mut impl Write, class: &str, end: f64) -> Result<()> {
+    writeln!(writer, "File type = \"ooTextFile\"")?;
+    writeln!(writer, "Object class = \"{}\"", class)?;
+    writeln!(writer)?;
+    writeln!(writer, "xmin = 0 ")?;
+    writeln!(writer, "xmax = {} ", end)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals;
+
+    // The (xmin, xmax) of every interval in a TextGrid file
+    fn intervals(path: &Path) -> Vec<(f64, f64)> {
+        let text = std::fs::read_to_string(path).unwrap();
+        let mut lines = text.lines().skip_while(|line| !line.contains("intervals ["));
+        let mut intervals = Vec::new();
+        while let (Some(_), Some(xmin), Some(xmax), Some(_)) = (lines.next(), lines.next(), lines.next(), lines.next()) {
+            let value = |line: &str| line.trim().trim_start_matches("xmin =").trim_start_matches("xmax =").trim().parse::<f64>().unwrap();
+            intervals.push((value(xmin), value(xmax)));
+        }
+        intervals
+    }
+
+    #[test]
+    fn text_grid_intervals_tile_the_clip() {
+        let sample_rate = 16000;
+        let mut samples = test_signals::noise(0.5, 0.001, sample_rate, 1);
+        samples.extend(test_signals::harmonic(150.0, 1.0, sample_rate));
+        samples.extend(test_signals::noise(0.23, 0.001, sample_rate, 2));
+        samples.extend(test_signals::harmonic(150.0, 1.0, sample_rate));
+        samples.extend(test_signals::noise(0.5, 0.001, sample_rate, 3));
+        let audio_clip = test_signals::clip(samples, sample_rate);
+        let speech = audio_clip.speech().unwrap();
+        let analysis = Analysis::of_speech(&audio_clip, &speech);
+        let end = audio_clip.duration().unwrap();
+
+        // Segments straight from the detector, and ones that touch and overlap like those d9a48e3 had to handle
+        let overlapping = Speech { samples: speech.samples.clone(), segments: vec![8000..25000, 24000..30000, 30000..45000] };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.TextGrid");
+        for speech in [&speech, &overlapping] {
+            export(&audio_clip, speech, &analysis, TrackFormat::TextGrid, false, &path).unwrap();
+            let intervals = intervals(&path);
+            assert_eq!(intervals.first().unwrap().0, 0.0);
+            assert_eq!(intervals.last().unwrap().1, end);
+            assert!(intervals.iter().all(|(start, stop)| start < stop), "{:?}", intervals);
+            assert!(intervals.windows(2).all(|pair| pair[0].1 == pair[1].0), "{:?}", intervals);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::ops::Range;
+
+/// Voice activity detection on mono samples
+use crate::dsp::{self, Spectrum};
+
+/// Tuning for the voice activity detector
+#[derive(Debug, Clone, Copy)]
+pub struct VadConfig {
+    /// Analysis frame length in seconds
+    pub frame: f32,
+    /// Step between frames in seconds
+    pub hop: f32,
+    /// How far above the estimated noise floor a frame must be to count as speech, in dB
+    pub threshold_db: f32,
+    /// Frames quieter than this are never speech, in dBFS
+    pub min_level_db: f32,
+    /// Frames flatter than this (white noise is 1.0) need a strong speech band to count as speech
+    pub max_flatness: f32,
+    /// Share of the frame energy that must fall in the 300-3400 Hz speech band
+    pub min_speech_band: f32,
+    /// Consecutive speech frames needed before a segment starts, in seconds
+    pub onset: f32,
+    /// How long a segment stays open after the last speech frame, in seconds
+    pub hangover: f32,
+}
+
+impl Default for VadConfig {
+    fn default() -> VadConfig {
+        VadConfig {
+            frame: 0.03,
+            hop: 0.01,
+            threshold_db: 12.0,
+            min_level_db: -55.0,
+            max_flatness: 0.5,
+            min_speech_band: 0.6,
+            onset: 0.03,
+            hangover: 0.2,
+        }
+    }
+}
+
+/// Find the ranges of `samples` that contain speech
+pub fn speech_segments(samples: &[f32], sample_rate: u32, config: &VadConfig) -> Vec<Range<usize>> {
+    let frame_len = (config.frame * sample_rate as f32) as usize;
+    let hop = ((config.hop * sample_rate as f32) as usize).max(1);
+    let mut spectrum = Spectrum::new(frame_len);
+    let bin_hz = sample_rate as f32 / frame_len as f32;
+
+    // Energy, spectral flatness and speech band ratio per frame
+    let features: Vec<(usize, f32, f32, f32)> = dsp::frames(samples, frame_len, hop)
+        .map(|(start, frame)| {
+            let magnitudes = spectrum.magnitudes(frame);
+            let power: Vec<f32> = magnitudes.iter().map(|m| m * m + 1e-12).collect();
+            let total: f32 = power.iter().sum();
+            let mean_log = power.iter().map(|p| p.ln()).sum::<f32>() / power.len() as f32;
+            let flatness = mean_log.exp() / (total / power.len() as f32);
+            let band: f32 = power.iter().enumerate()
+                .filter(|(bin, _)| (300.0..=3400.0).contains(&(*bin as f32 * bin_hz)))
+                .map(|(_, p)| p)
+                .sum();
+            (start, dsp::rms_db(frame), flatness, band / total)
+        })
+        .collect();
+    if features.is_empty() {
+        return Vec::new();
+    }
+
+    // Estimate the noise floor from the quietest tenth of the frames
+    let mut levels: Vec<f32> = features.iter().map(|f| f.1).collect();
+    levels.sort_by(f32::total_cmp);
+    let noise_floor = levels[levels.len() / 10];
+    let threshold = (noise_floor + config.threshold_db).max(config.min_level_db);
+
+    let onset_frames = ((config.onset / config.hop).round() as usize).max(1);
+    let hangover_frames = (config.hangover / config.hop).round() as usize;
+    let mut segments: Vec<Range<usize>> = Vec::new();
+    let mut run = 0;
+    let mut silent_for = 0;
+    let mut current: Option<Range<usize>> = None;
+    for (i, &(start, level, flatness, band)) in features.iter().enumerate() {
+        let is_speech = level > threshold && (flatness < config.max_flatness || band > config.min_speech_band);
+        run = if is_speech { run + 1 } else { 0 };
+        match current.as_mut() {
+            Some(segment) if is_speech => {
+                segment.end = start + frame_len;
+                silent_for = 0;
+            }
+            Some(_) => {
+                silent_for += 1;
+                if silent_for > hangover_frames {
+                    segments.extend(current.take());
+                }
+            }
+            None if run >= onset_frames => {
+                current = Some(features[i + 1 - run].0..start + frame_len);
+                silent_for = 0;
+            }
+            None => {}
+        }
+    }
+    segments.extend(current);
+
+    // Let each segment run on for the hangover so word endings are kept, without running into the next one
+    let hangover_samples = hangover_frames * hop;
+    for i in 0..segments.len() {
+        let limit = segments.get(i + 1).map_or(samples.len(), |next| next.start);
+        segments[i].end = (segments[i].end + hangover_samples).min(limit);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals;
+
+    #[test]
+    fn close_utterances_give_disjoint_segments() {
+        let sample_rate = 16000;
+        let config = VadConfig::default();
+        // Gaps around the point where the first segment just closes before the second starts
+        for gap_ms in (200..=300).step_by(10) {
+            let mut samples = test_signals::noise(0.5, 0.001, sample_rate, 1);
+            samples.extend(test_signals::harmonic(150.0, 1.0, sample_rate));
+            samples.extend(test_signals::noise(gap_ms as f64 / 1000.0, 0.001, sample_rate, 2));
+            samples.extend(test_signals::harmonic(150.0, 1.0, sample_rate));
+            samples.extend(test_signals::noise(0.5, 0.001, sample_rate, 3));
+
+            let segments = speech_segments(&samples, sample_rate, &config);
+            assert!(!segments.is_empty());
+            assert!(segments.windows(2).all(|pair| pair[0].end <= pair[1].start), "{} ms gap: {:?}", gap_ms, segments);
+            assert!(segments.iter().all(|segment| segment.start < segment.end && segment.end <= samples.len()));
+        }
+    }
+}